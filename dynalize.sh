@@ -77,7 +77,11 @@ CLEANUP_FILES="$CLEANUP_FILES $DEST_TG"
 valgrind --tool=taintgrind --tainted-ins-only=yes "$EXEC" > /dev/null 2> "$DEST_TG"
 
 $PROCESS_TOOL $PTO_ARGS "$DEST_TG"
+STATUS=$?
 
 if [ $CLEANUP = 1 ]; then
     rm -f $CLEANUP_FILES
 fi
+
+# 0: no findings, 1: findings, 2: the log could not be processed
+exit $STATUS
@@ -13,7 +13,7 @@ def allTests():
     """
     This function returns a list of tests.
     """
-    tests = make_tests("instr_bop", "../dynalize.sh", True, ["--no-color"])
+    tests = make_tests("instr_bop", "../dynalize.sh", True, ["--no-color", "-q"])
     
     for test in tests:
         # mark optionals
@@ -21,6 +21,8 @@ def allTests():
             test.opt()
 
         test.timeoutFactor = 5
+        # tgproc exits with 1 if it reports findings
+        test.expectFindingsExitCode()
             
     return tests
 
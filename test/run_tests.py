@@ -14,6 +14,9 @@ Command line options:
      Default is 4
  -L, --valgrind
      Use valgrind to check for memory leaks during testing
+ -u, --update
+     Write the output of each test to its expected .output file instead of
+     comparing them, e.g. after the output format changed
 """
 
 import infrastructure.tests
@@ -40,7 +43,7 @@ def main():
     
     # get cmd file
     try:
-        opts, args = getopt.getopt(sys.argv[1:], "he:t:j:L", ["help", "executable", "timeout", "jobs", "valgrind"])
+        opts, args = getopt.getopt(sys.argv[1:], "he:t:j:Lu", ["help", "executable", "timeout", "jobs", "valgrind", "update"])
     except getopt.error as msg:
         print(msg)
         sys.exit(2)
@@ -56,6 +59,8 @@ def main():
             valgrind = True
         if o in ("-j", ):
             processes = int(a)
+        if o in ("-u", "--update"):
+            infrastructure.tests.CompilerOutputTest.update = True
 
     if len(args) > 1:
         print("You specified too many arguments.")
@@ -1,46 +1,121 @@
 extern crate argparse;
 
-use self::argparse::{ArgumentParser, StoreTrue, StoreFalse, Store, StoreConst, Collect};
+use std::env;
+use std::io::{stdout, stderr};
+use std::process;
+use graph::term;
+use graph::summary::EXIT_ERROR;
+use self::argparse::{ArgumentParser, StoreTrue, StoreFalse, Store, StoreConst, Collect, List};
 
+#[derive(Clone)]
 pub struct Options {
     pub taintgrind_trace: bool,
     pub mark_taint: bool,
     pub mark_trace: bool,
     pub no_libs: bool,
+    pub lib_globs: Vec<String>,
+    pub own_globs: Vec<String>,
     pub no_tmp_instr: bool,
     pub unique_locs: bool,
     pub single_sink: bool,
     pub single_src: bool,
     pub src_only: bool,
+    pub sources: String,
+    pub context: usize,
+    pub backtrace: bool,
     pub color: bool,
+    pub force_color: bool,
+    pub pager: bool,
+    pub compare_opt: bool,
     pub verbosity: u8,
     pub sink_lines: Vec<usize>,
-    pub logfile: String
+    pub baseline: String,
+    pub write_baseline: String,
+    pub fail_on: String,
+    pub format: String,
+    pub command: String,
+    pub html_dir: String,
+    pub annotate_file: String,
+    pub export_graph: String,
+    pub load_map: String,
+    pub simple_names: bool,
+    pub source_roots: Vec<String>,
+    pub path_maps: Vec<String>,
+    pub outputs: Vec<String>,
+    pub logfile: String,
+    pub logfiles: Vec<String>
+}
+
+/// The commands that can be given as first argument
+const COMMANDS: [&str; 2] = ["report", "annotate"];
+
+impl Options {
+    /// The options without any arguments
+    pub fn new() -> Options {
+        Options {
+            taintgrind_trace: false,
+            mark_taint: false,
+            mark_trace: false,
+            no_libs: true,
+            lib_globs: vec![],
+            own_globs: vec![],
+            no_tmp_instr: true,
+            unique_locs: false,
+            single_sink: false,
+            single_src: false,
+            src_only: false,
+            sources: "all".to_string(),
+            context: 0,
+            backtrace: false,
+            color: true,
+            force_color: false,
+            pager: true,
+            compare_opt: false,
+            verbosity: 10,
+            sink_lines: vec![],
+            baseline: "".to_string(),
+            write_baseline: "".to_string(),
+            fail_on: "all".to_string(),
+            format: "text".to_string(),
+            command: "".to_string(),
+            html_dir: "".to_string(),
+            annotate_file: "".to_string(),
+            export_graph: "".to_string(),
+            load_map: "".to_string(),
+            simple_names: false,
+            source_roots: vec![],
+            path_maps: vec![],
+            outputs: vec![],
+            logfile: "".to_string(),
+            logfiles: vec![]
+        }
+    }
 }
 
 pub fn parse_opts() -> Options {
     // parse cli options
-    let mut cli_opts = Options {
-        taintgrind_trace: false,
-        mark_taint: false,
-        mark_trace: false,
-        no_libs: true,
-        no_tmp_instr: true,
-        unique_locs: false,
-        single_sink: false,
-        single_src: false,
-        src_only: false,
-        color: true,
-        verbosity: 10,
-        sink_lines: vec![],
-        logfile: "".to_string()
-    };
+    let mut cli_opts = Options::new();
+
+    // the optional command is the first argument
+    let mut args: Vec<String> = env::args().collect();
+    if args.len() > 1 && COMMANDS.contains(&args[1].as_str()) {
+        cli_opts.command = args.remove(1);
+    }
     
     {
         let mut ap = ArgumentParser::new();
         
         ap.set_description("This finds traces in the taintgrind output that lead to \
-                            dangerous behavior.");
+                            dangerous behavior. The exit code is 0 if nothing was \
+                            found, 1 if there are findings and 2 if the input \
+                            could not be read or parsed. \
+                            Use 'tgproc report --html <dir> <log>' to write a \
+                            static HTML report instead and \
+                            'tgproc annotate <file.c> <log>' to print the source \
+                            file with the taint and hit count of each line. \
+                            The results of debug info lookups are cached in \
+                            $TGPROC_CACHE_DIR or ~/.cache/tgproc; set \
+                            TGPROC_CACHE_DIR to an empty value to disable that.");
         
         ap.refer(&mut cli_opts.verbosity)
             .add_option(&["-v", "--verbose"], StoreConst(20),
@@ -51,9 +126,27 @@ pub fn parse_opts() -> Options {
         ap.refer(&mut cli_opts.no_libs)
             .add_option(&["--libs"], StoreFalse,
                         "In the traces, show lines that are located in 3rd-party-libraries. \
-                         Specifically, a line is considered to be in a \
-                         library, if the source file cannot be found below the \
-                         current working directory.");
+                         Which lines are library code is decided by --lib-glob and \
+                         --own-glob; by default the lines in shared objects (*.so, \
+                         *.so.*) without debug info in the log are.");
+        
+        ap.refer(&mut cli_opts.lib_globs)
+            .add_option(&["--lib-glob"], Collect,
+                        "Treat the locations that match the glob as library code; the \
+                         glob is matched against the object path (obj:/usr/lib/*), the \
+                         source file from the debug info or as found below the \
+                         source roots relative to the current directory \
+                         (src:third_party/*) or the function name \
+                         (func:std::*); can be given several times and replaces the \
+                         default obj:*.so and obj:*.so.*")
+            .metavar("kind:glob");
+        
+        ap.refer(&mut cli_opts.own_globs)
+            .add_option(&["--own-glob"], Collect,
+                        "Treat the locations that match the glob as our code even if a \
+                         --lib-glob matches, e.g. obj:*/libfoo.so; same syntax as \
+                         --lib-glob. -v shows the decision for each node.")
+            .metavar("kind:glob");
         
         ap.refer(&mut cli_opts.no_tmp_instr)
             .add_option(&["--tmp-instr"], StoreFalse,
@@ -74,6 +167,27 @@ pub fn parse_opts() -> Options {
             .add_option(&["--single-sink"], StoreTrue,
                         "Show only one trace per sink.");
         
+        ap.refer(&mut cli_opts.sources)
+            .add_option(&["--sources"], Store,
+                        "Only show the traces whose source is in memory of the comma \
+                         separated classes: stack, heap, global or unknown (default: all)")
+            .metavar("classes");
+        
+        ap.refer(&mut cli_opts.context)
+            .add_option(&["-C", "--context"], Store,
+                        "Show N lines of source code before and after the source \
+                         and the sink of each trace in the text output")
+            .metavar("N");
+        
+        ap.refer(&mut cli_opts.backtrace)
+            .add_option(&["--backtrace"], StoreTrue,
+                        "Show the functions each line of a trace is called from and \
+                         the backtrace of the sink in the text output. Taintgrind \
+                         does not log calls, so they are inferred from the changes \
+                         of the function and marked as reconstructed; e.g. a \
+                         function called right after another one returned looks \
+                         like it was called by that one.");
+        
         ap.refer(&mut cli_opts.src_only)
             .add_option(&["--src-only"], StoreTrue,
                         "Show only the sources, not the full trace.");
@@ -91,21 +205,124 @@ pub fn parse_opts() -> Options {
             .add_option(&["--mark-taint"], StoreTrue,
                         "Just mark the taint color of each line");
         
+        ap.refer(&mut cli_opts.simple_names)
+            .add_option(&["--simple-names"], StoreTrue,
+                        "Show the function names without template arguments, \
+                         e.g. std::vector::push_back(int const&)");
+        
         ap.refer(&mut cli_opts.color)
             .add_option(&["--no-color"], StoreFalse,
-                        "Do not use terminal colors");
+                        "Do not use terminal colors; by default they are used if \
+                         stdout is a terminal and NO_COLOR is not set");
+        
+        ap.refer(&mut cli_opts.force_color)
+            .add_option(&["--color"], StoreTrue,
+                        "Use terminal colors even if stdout is not a terminal");
+        
+        ap.refer(&mut cli_opts.pager)
+            .add_option(&["--no-pager"], StoreFalse,
+                        "Do not pipe the text output through $PAGER (default: less) \
+                         if stdout is a terminal");
+        
+        ap.refer(&mut cli_opts.compare_opt)
+            .add_option(&["--compare-opt"], StoreTrue,
+                        "The logs are from several builds of the same program \
+                         (e.g. foo.O0.output foo.O3.output); print a matrix of \
+                         which sink was found at which optimisation level");
+        
+        ap.refer(&mut cli_opts.format)
+            .add_option(&["--format"], Store,
+                        "The output format: text, json (one document with all sinks), \
+                         jsonl (one sink per line), sarif (SARIF 2.1.0), dot (a graphviz \
+                         digraph with the subgraph of each sink as a cluster), gcc (compiler style \
+                         diagnostics) or junit (JUnit XML with one testsuite per log) \
+                         (default: text)")
+            .metavar("format");
+        
+        ap.refer(&mut cli_opts.outputs)
+            .add_option(&["-o", "--output"], Collect,
+                        "Write the output in the format to the path, e.g. json:out.json, \
+                         html:report/ or text:- for stdout; can be given several times \
+                         to render several formats from one parse of the log (formats: \
+                         text, json, jsonl, sarif, dot, gcc, junit, html)")
+            .metavar("format:path");
+        
+        ap.refer(&mut cli_opts.html_dir)
+            .add_option(&["--html"], Store,
+                        "report: the directory to write the HTML report to")
+            .metavar("dir");
+        
+        ap.refer(&mut cli_opts.export_graph)
+            .add_option(&["--export-graph"], Store,
+                        "Write all kept nodes and edges of the graph to the file while \
+                         the log is parsed; GraphML if the file ends in .graphml, \
+                         JSON Lines otherwise")
+            .metavar("file");
+        
+        ap.refer(&mut cli_opts.load_map)
+            .add_option(&["--load-map"], Store,
+                        "A copy of /proc/<pid>/maps of the analysed process to translate \
+                         the runtime addresses of PIE executables and shared objects; \
+                         without it the messages of 'valgrind -v' in the log are used")
+            .metavar("file");
+        
+        ap.refer(&mut cli_opts.source_roots)
+            .add_option(&["--source-root"], Collect,
+                        "A directory to search the source files in; can be given several \
+                         times, the directories are searched in that order (default: .)")
+            .metavar("dir");
+        
+        ap.refer(&mut cli_opts.path_maps)
+            .add_option(&["--path-map"], Collect,
+                        "Replace the prefix old of the source paths in the debug info by \
+                         new, like -fdebug-prefix-map; can be given several times, the \
+                         first matching one is used")
+            .metavar("old=new");
         
         ap.refer(&mut cli_opts.sink_lines)
             .add_option(&["--mark-sink"], Collect,
                         "Mark the line as sink; this disables automatic sink detection")
             .metavar("lineno");
 
-        ap.refer(&mut cli_opts.logfile)
-            .add_argument("<taintgrind log>", Store,
-                          "The taintgrind log file")
+        ap.refer(&mut cli_opts.baseline)
+            .add_option(&["--baseline"], Store,
+                        "Hide the findings that are listed in the baseline file \
+                         and show only new ones")
+            .metavar("file");
+
+        ap.refer(&mut cli_opts.write_baseline)
+            .add_option(&["--write-baseline"], Store,
+                        "Write the fingerprints of all findings to the baseline file")
+            .metavar("file");
+
+        ap.refer(&mut cli_opts.fail_on)
+            .add_option(&["--fail-on"], Store,
+                        "Comma separated list of the sink kinds (deref, store, exit, \
+                         cond, manual) whose findings make tgproc exit with code 1; \
+                         'all' or 'none' are also accepted (default: all)")
+            .metavar("kinds");
+
+        ap.refer(&mut cli_opts.logfiles)
+            .add_argument("<taintgrind log>", List,
+                          "The taintgrind log file; if several logs are given \
+                           the findings of all runs are merged")
             .required();
         
-        ap.parse_args_or_exit();
+        if let Err(code) = ap.parse(args, &mut stdout(), &mut stderr()) {
+            process::exit(code);
+        }
     }
+
+    if cli_opts.command == "annotate" {
+        // the first positional argument is the source file
+        cli_opts.annotate_file = cli_opts.logfiles.remove(0);
+        if cli_opts.logfiles.is_empty() {
+            eprintln!("Usage: tgproc annotate <source file> <taintgrind log>");
+            process::exit(EXIT_ERROR);
+        }
+    }
+
+    cli_opts.color = cli_opts.force_color || (cli_opts.color && term::use_color());
+    cli_opts.logfile = cli_opts.logfiles[0].clone();
     cli_opts
 }
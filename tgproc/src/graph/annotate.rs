@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Error;
+use std::io::Result;
+use std::io::Write;
+use std::path::Path;
+
+use super::Graph;
+use super::TgNode;
+use super::tgnode::Taint;
+use super::meta::SrcLoc;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+
+/// What happened at one source location in the whole log
+pub struct LineStats {
+    loc: SrcLoc,
+    pub hits: usize,
+    pub taint: Taint, // the strongest taint that was seen
+    pub sink: bool
+}
+
+/// The locations are identified by file, line and function as given in the log.
+/// If the line is not known the address is part of the key, too, so that it can
+/// be looked up in the debug info later.
+pub type LineStatsMap = HashMap<(String, Option<usize>, String, u64), LineStats>;
+
+pub fn add_line_stats(stats: &mut LineStatsMap, loc: &SrcLoc, node: &TgNode) {
+    let addr = if loc.lineno.is_some() { 0 } else { loc.addr };
+    let entry = stats.entry((loc.file.clone(), loc.lineno, loc.func.clone(), addr))
+        .or_insert_with(|| LineStats { loc: loc.clone(), hits: 0, taint: Taint::Green, sink: false });
+
+    entry.hits += 1;
+    entry.sink |= node.is_sink();
+    if node.taint.strength() > entry.taint.strength() {
+        entry.taint = node.taint;
+    }
+}
+
+/// Prints a source file like gcov: each line is prefixed with the strongest
+/// taint seen there, the number of log lines for it and whether it hosts a sink.
+/// It reports no findings, so the run only fails if the file cannot be read.
+pub struct Annotator<'a> {
+    graph: &'a Graph,
+    file: String,
+    findings: Findings
+}
+
+impl<'a> Annotator<'a> {
+    pub fn new(graph: &'a Graph, file: &str) -> Annotator<'a> {
+        Annotator {
+            graph,
+            file: file.to_string(),
+            findings: Findings::new()
+        }
+    }
+
+    fn print(&mut self, out: &mut dyn Write) -> Result<()> {
+        let file = self.file.clone();
+        let src = fs::read_to_string(&file).map_err(|e| Error::new(e.kind(), format!("{}: {}", file, e)))?;
+
+        // lineno -> (hits, taint, sink)
+        let mut lines: HashMap<usize, (usize, Taint, bool)> = HashMap::new();
+
+        for stats in self.graph.line_stats.as_ref().unwrap().values() {
+            let mut loc = stats.loc.clone();
+            loc.complete_info(&mut self.findings.debug_db);
+
+            if let Some(lineno) = loc.lineno {
+                if Annotator::same_file(loc.path(), &file) {
+                    let entry = lines.entry(lineno).or_insert((0, Taint::Green, false));
+                    entry.0 += stats.hits;
+                    if stats.taint.strength() > entry.1.strength() {
+                        entry.1 = stats.taint;
+                    }
+                    entry.2 |= stats.sink;
+                }
+            }
+        }
+
+        for (idx, line) in src.lines().enumerate() {
+            let lineno = idx + 1;
+            let (prefix, taint) = match lines.get(&lineno) {
+                Some(&(hits, taint, sink)) => {
+                    (format!("{} {:>8} {}", taint.abbrv(), hits, if sink { "S" } else { " " }), Some(taint))
+                },
+                None => (format!("  {:>8}  ", "-"), None)
+            };
+
+            let text = format!("{}:{:>5}:{}", prefix, lineno, line);
+            match taint {
+                Some(ref t) if self.graph.options.color => writeln!(out, "{}", t.paint(&text))?,
+                _ => writeln!(out, "{}", text)?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn same_file(a: &Path, file: &str) -> bool {
+        let b = Path::new(file);
+        match (a.canonicalize(), b.canonicalize()) {
+            (Ok(ca), Ok(cb)) => ca == cb,
+            _ => a.ends_with(b) || b.ends_with(a)
+        }
+    }
+}
+
+impl<'a> Renderer for Annotator<'a> {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.findings.reset(self.graph);
+        self.print(out)
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
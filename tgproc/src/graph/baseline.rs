@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufRead;
+use std::io::Write;
+use std::io::Result;
+
+use super::SinkKind;
+use super::meta::SrcLoc;
+use super::meta::sources;
+
+/// A set of known findings. Each finding is stored as a fingerprint that only
+/// consists of source locations, so it stays the same between runs even though
+/// the taintgrind line numbers and the names of the temporaries change. The
+/// files are relative to the current directory if the sources are found below
+/// it and the functions are not simplified, so that a baseline can be shared
+/// between checkouts and machines.
+///
+/// The baseline file contains one fingerprint per line, e.g.
+///     deref sample.c:main:8 <- sample.c:main:5
+/// Empty lines and lines starting with # are ignored.
+#[derive(Clone)]
+pub struct Baseline(HashSet<String>);
+
+impl Baseline {
+    pub fn new() -> Baseline {
+        Baseline(HashSet::new())
+    }
+
+    pub fn load(path: &str) -> Result<Baseline> {
+        let mut baseline = Baseline::new();
+
+        let f = File::open(path)?;
+        for line in BufReader::new(&f).lines() {
+            let l = line?;
+            let fp = l.trim();
+            if !fp.is_empty() && !fp.starts_with('#') {
+                baseline.insert(fp.to_string());
+            }
+        }
+
+        Ok(baseline)
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let Baseline(ref fps) = *self;
+
+        // sort the fingerprints so that the file can be diffed
+        let mut sorted: Vec<&String> = fps.iter().collect();
+        sorted.sort();
+
+        let mut f = File::create(path)?;
+        writeln!(f, "# tgproc baseline: <sink kind> <sink location> <- <source location>")?;
+        for fp in sorted {
+            writeln!(f, "{}", fp)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, fp: String) {
+        let Baseline(ref mut fps) = *self;
+        fps.insert(fp);
+    }
+
+    pub fn contains(&self, fp: &str) -> bool {
+        let Baseline(ref fps) = *self;
+        fps.contains(fp)
+    }
+
+    pub fn fingerprint(kind: SinkKind, sink: &SrcLoc, source: &SrcLoc) -> String {
+        format!("{} {} <- {}", kind.name(), Baseline::loc_fingerprint(sink), Baseline::loc_fingerprint(source))
+    }
+
+    fn loc_fingerprint(loc: &SrcLoc) -> String {
+        let file = match loc.src_path {
+            Some(_) => loc.project_path().to_string_lossy().into_owned(),
+            None => sources::remap(&loc.file)
+        };
+        let file = file.trim_start_matches("./");
+        let func = loc.full_func.as_ref().unwrap_or(&loc.func);
+        match loc.lineno {
+            Some(lineno) => format!("{}:{}:{}", file, func, lineno),
+            None => format!("{}:{}:?", file, func)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn loc(file: &str, lineno: Option<usize>, func: &str) -> SrcLoc {
+        SrcLoc::new_u64(0x1000, file.to_string(), lineno, func.to_string())
+    }
+
+    #[test]
+    fn fingerprints() {
+        let sink = loc("./sample.c", Some(8), "main");
+        let src = loc("sample.c", None, "f");
+        assert_eq!(Baseline::fingerprint(SinkKind::Deref, &sink, &src), "deref sample.c:main:8 <- sample.c:f:?");
+    }
+
+    #[test]
+    fn fingerprints_are_independent_of_the_checkout() {
+        // the same source found below two different build directories
+        let mut a = loc("/build/a/src/main.rs", Some(3), "main");
+        a.src_path = Some(env::current_dir().unwrap().join("src/main.rs"));
+        let mut b = loc("/home/ci/b/src/main.rs", Some(3), "main");
+        b.src_path = Some(PathBuf::from("src/main.rs"));
+        assert_eq!(Baseline::loc_fingerprint(&a), "src/main.rs:main:3");
+        assert_eq!(Baseline::loc_fingerprint(&b), "src/main.rs:main:3");
+
+        // --simple-names does not change the fingerprint
+        let mut simple = loc("src/main.rs", Some(3), "std::vector::push_back(int const&)");
+        simple.full_func = Some("std::vector<int, std::allocator<int> >::push_back(int const&)".to_string());
+        assert_eq!(Baseline::loc_fingerprint(&simple),
+                   "src/main.rs:std::vector<int, std::allocator<int> >::push_back(int const&):3");
+    }
+
+    #[test]
+    fn write_and_load() {
+        let path = env::temp_dir().join(format!("tgproc-baseline-{}.txt", process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut baseline = Baseline::new();
+        baseline.insert("deref b.c:main:8 <- b.c:main:5".to_string());
+        baseline.insert("exit a.c:main:3 <- a.c:f:?".to_string());
+        baseline.write(path).unwrap();
+
+        let text = fs::read_to_string(path).unwrap();
+        let loaded = Baseline::load(path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(text.lines().skip(1).collect::<Vec<&str>>(),
+                   ["deref b.c:main:8 <- b.c:main:5", "exit a.c:main:3 <- a.c:f:?"]);
+        let loaded = loaded.unwrap();
+        assert!(loaded.contains("deref b.c:main:8 <- b.c:main:5"));
+        assert!(loaded.contains("exit a.c:main:3 <- a.c:f:?"));
+        assert!(!loaded.contains("deref b.c:main:9 <- b.c:main:5"));
+        assert!(!loaded.contains("# tgproc baseline: <sink kind> <sink location> <- <source location>"));
+    }
+}
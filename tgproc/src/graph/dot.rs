@@ -0,0 +1,125 @@
+use std::io::Result;
+use std::io::Write;
+
+use super::Graph;
+use super::TgNode;
+use super::tgnode::Taint;
+use super::meta::TgMetaDb;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+
+/// Prints for each sink the subgraph of all non-green nodes that reach it in
+/// the graphviz dot format. Other than the traces this also shows where several
+/// flows meet, e.g. when two blue pointers are added. All sinks are clusters of
+/// one digraph; a node that reaches several sinks is shown in each cluster.
+pub struct DotPrinter<'a, T: 'a + TgMetaDb> {
+    graph: &'a Graph,
+    meta_db: &'a mut T,
+    findings: Findings
+}
+
+impl<'a, T: TgMetaDb> DotPrinter<'a, T> {
+    pub fn new(graph: &'a Graph, meta_db: &'a mut T) -> DotPrinter<'a, T> {
+        DotPrinter {
+            graph,
+            meta_db,
+            findings: Findings::new()
+        }
+    }
+
+    fn print_subgraph(&mut self, out: &mut dyn Write, sink: &TgNode) -> Result<()> {
+        let traces = self.findings.traces_of(self.graph, self.meta_db, sink);
+        self.findings.count_all(sink.sink_kind.unwrap(), &traces);
+        if !traces.is_empty() && traces.iter().all(|t| t.suppression.is_some()) {
+            // suppressed by asb-ignore or the baseline
+            return Ok(());
+        }
+        let (nodes, edges) = self.graph.get_subgraph(sink);
+
+        // the node ids are prefixed with the sink, so that the clusters don't share nodes
+        let sink_id = sink.idx + 1;
+        writeln!(out, "    subgraph cluster_{} {{", sink_id)?;
+        writeln!(out, "        label=\"{}\";", DotPrinter::<T>::escape(&format!("{} at taintgrind log line {}",
+                                                                            sink.sink_kind.unwrap().description(),
+                                                                            sink_id)))?;
+
+        for node in nodes.iter() {
+            // the transition from blue to red is where the address sensitive behavior starts
+            let transition = node.is_red() &&
+                edges.iter().any(|&(pred, succ, _)| succ == *node && pred.is_blue());
+
+            let label = match self.meta_db.get_mut(node) {
+                Some(meta) => {
+                    meta.loc.complete_info(&mut self.findings.debug_db);
+                    format!("{}: {}:{}{}\n{}",
+                            node.idx + 1,
+                            meta.loc.file,
+                            meta.loc.lineno.map_or("?".to_string(), |l| l.to_string()),
+                            node.source_class.map_or(String::new(), |c| format!(" ({})", c.name())),
+                            meta.loc.src_line.as_ref().unwrap_or(&meta.line))
+                },
+                None => format!("{}", node.idx + 1)
+            };
+
+            let mut attrs = vec![format!("label=\"{}\"", DotPrinter::<T>::escape(&label)),
+                                 format!("fillcolor=\"{}\"", DotPrinter::<T>::fill_color(&node.taint))];
+            if node.is_sink() {
+                attrs.push("shape=doubleoctagon".to_string());
+            } else if node.is_source() {
+                attrs.push("shape=house".to_string());
+            }
+            if transition {
+                attrs.push("penwidth=3".to_string());
+                attrs.push("color=\"red\"".to_string());
+            }
+
+            writeln!(out, "        s{}_n{} [{}];", sink_id, node.idx + 1, attrs.join(", "))?;
+        }
+
+        for &(pred, succ, via) in edges.iter() {
+            // label the edge with the name of the C variable if it is known
+            let label = match self.meta_db.get(pred) {
+                Some(meta) if meta.var.as_deref() == Some(via) => meta.var_label().unwrap(),
+                _ => via.to_string()
+            };
+            writeln!(out, "        s{0}_n{1} -> s{0}_n{2} [label=\"{3}\"];",
+                     sink_id, pred.idx + 1, succ.idx + 1, DotPrinter::<T>::escape(&label))?;
+        }
+
+        writeln!(out, "    }}")
+    }
+
+    fn fill_color(taint: &Taint) -> &str {
+        match *taint {
+            Taint::Red => "#ffb0b0",
+            Taint::Blue => "#b0c8ff",
+            Taint::Green => "#b0ffb0"
+        }
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+    }
+}
+
+impl<'a, T: TgMetaDb> Renderer for DotPrinter<'a, T> {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.findings.reset(self.graph);
+
+        writeln!(out, "digraph sinks {{")?;
+        writeln!(out, "    node [shape=box, style=filled, fontname=\"monospace\"];")?;
+        for sink in self.graph.sinks.iter() {
+            self.print_subgraph(out, sink)?;
+        }
+        writeln!(out, "}}")
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
@@ -0,0 +1,144 @@
+use serde_json;
+use serde_json::Value;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Result;
+use std::io::Write;
+
+use super::TgNode;
+use super::meta::TgMetaNode;
+use super::html::escape;
+
+const GRAPHML_KEYS: [(&str, &str, &str); 13] = [
+    ("node", "taint", "string"),
+    ("node", "source", "boolean"),
+    ("node", "sink", "boolean"),
+    ("node", "sink_kind", "string"),
+    ("node", "source_class", "string"),
+    ("node", "var", "string"),
+    ("node", "var_name", "string"),
+    ("node", "file", "string"),
+    ("node", "func", "string"),
+    ("node", "lineno", "int"),
+    ("node", "addr", "long"),
+    ("node", "line", "string"),
+    ("edge", "via", "string")
+];
+
+/// Writes every kept node and its incoming edges to a file while the graph is
+/// built, so that the export does not need the whole graph in memory. Files
+/// ending in .graphml are written as GraphML, all others as JSON Lines with
+/// one node or edge object per line.
+pub struct GraphExporter {
+    out: BufWriter<File>,
+    graphml: bool
+}
+
+impl GraphExporter {
+    pub fn new(path: &str, logfile: &str) -> Result<GraphExporter> {
+        let mut exporter = GraphExporter {
+            out: BufWriter::new(File::create(path)?),
+            graphml: path.ends_with(".graphml")
+        };
+
+        if exporter.graphml {
+            writeln!(exporter.out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+            writeln!(exporter.out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+            for &(target, name, ty) in GRAPHML_KEYS.iter() {
+                writeln!(exporter.out, "  <key id=\"{1}\" for=\"{0}\" attr.name=\"{1}\" attr.type=\"{2}\"/>",
+                         target, name, ty)?;
+            }
+            writeln!(exporter.out, "  <graph id=\"{}\" edgedefault=\"directed\">", escape(logfile))?;
+        }
+
+        Ok(exporter)
+    }
+
+    /// The nodes are identified by their line in the taintgrind log. The edges
+    /// point in the direction of the data flow, i.e. from the pred to the node.
+    pub fn add_node(&mut self, node: &TgNode, meta: &TgMetaNode) -> Result<()> {
+        if self.graphml {
+            writeln!(self.out, "    <node id=\"n{}\">", node.idx + 1)?;
+            self.write_data("taint", node.taint.name())?;
+            self.write_data("source", &node.is_source().to_string())?;
+            self.write_data("sink", &node.is_sink().to_string())?;
+            if let Some(kind) = node.sink_kind {
+                self.write_data("sink_kind", kind.name())?;
+            }
+            if let Some(class) = node.source_class {
+                self.write_data("source_class", class.name())?;
+            }
+            if let Some(ref var) = meta.var {
+                self.write_data("var", var)?;
+            }
+            if let Some(ref name) = meta.var_name {
+                self.write_data("var_name", name)?;
+            }
+            self.write_data("file", &meta.loc.file)?;
+            self.write_data("func", &meta.loc.func)?;
+            if let Some(lineno) = meta.loc.lineno {
+                self.write_data("lineno", &lineno.to_string())?;
+            }
+            self.write_data("addr", &meta.loc.addr.to_string())?;
+            self.write_data("line", &meta.line)?;
+            writeln!(self.out, "    </node>")?;
+        } else {
+            let doc = json!({
+                "type": "node",
+                "id": node.idx + 1,
+                "taint": node.taint.name(),
+                "source": node.is_source(),
+                "sink": node.is_sink(),
+                "sink_kind": node.sink_kind.map(|k| k.name()),
+                "source_class": node.source_class.map(|c| c.name()),
+                "var": meta.var,
+                "var_name": meta.var_name,
+                "file": meta.loc.file,
+                "func": meta.loc.func,
+                "lineno": meta.loc.lineno,
+                "addr": meta.loc.addr,
+                "line": meta.line
+            });
+            self.write_json(&doc)?;
+        }
+
+        let preds = node.preds.iter().filter_map(|edge| edge.dest.as_ref().map(|pred| (pred, edge.via.as_str())));
+        // the sink reasons are labelled with the sink kind like in the dot output
+        let kind = node.sink_kind.map_or("sink", |k| k.name());
+        let reasons = node.sink_reasons.iter().map(|pred| (pred, kind));
+
+        for (pred, via) in preds.chain(reasons) {
+            if self.graphml {
+                writeln!(self.out, "    <edge source=\"n{}\" target=\"n{}\"><data key=\"via\">{}</data></edge>",
+                         pred.idx + 1, node.idx + 1, escape(via))?;
+            } else {
+                let doc = json!({
+                    "type": "edge",
+                    "source": pred.idx + 1,
+                    "target": node.idx + 1,
+                    "via": via
+                });
+                self.write_json(&doc)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if self.graphml {
+            writeln!(self.out, "  </graph>\n</graphml>")?;
+        }
+        self.out.flush()
+    }
+
+    fn write_json(&mut self, doc: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.out, doc)?;
+        writeln!(self.out)
+    }
+
+    fn write_data(&mut self, key: &str, value: &str) -> Result<()> {
+        writeln!(self.out, "      <data key=\"{}\">{}</data>", key, escape(value))
+    }
+}
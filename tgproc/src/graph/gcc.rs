@@ -0,0 +1,107 @@
+use std::io::Result;
+use std::io::Write;
+
+use super::Graph;
+use super::TgNode;
+use super::meta::TgMetaDb;
+use super::meta::TgMetaNode;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+
+/// Prints each trace like a compiler diagnostic, i.e. a warning at the sink
+/// followed by a note for each step of the trace, e.g.
+///     sample.c:8: warning: red value reaches dereference [asb-deref]
+///     sample.c:5: note: blue value originates here in main: int *p = &i;
+/// This format is understood by the quickfix lists of most editors.
+pub struct GccPrinter<'a, T: 'a + TgMetaDb> {
+    graph: &'a Graph,
+    meta_db: &'a mut T,
+    findings: Findings
+}
+
+impl<'a, T: TgMetaDb> GccPrinter<'a, T> {
+    pub fn new(graph: &'a Graph, meta_db: &'a mut T) -> GccPrinter<'a, T> {
+        GccPrinter {
+            graph,
+            meta_db,
+            findings: Findings::new()
+        }
+    }
+
+    fn print_traces_of(&mut self, out: &mut dyn Write, sink: &TgNode) -> Result<()> {
+        let kind = sink.sink_kind.unwrap();
+
+        for trace in self.findings.traces_of(self.graph, self.meta_db, sink) {
+            self.findings.count(kind, &trace);
+            if trace.suppression.is_some() {
+                continue;
+            }
+
+            let sink_meta = self.meta_db.get(sink).unwrap();
+            writeln!(out, "{} warning: {} [asb-{}]", GccPrinter::<T>::position(sink_meta), kind.description(), kind.name())?;
+
+            let mut prev_meta: Option<&TgMetaNode> = None;
+            for node in trace.nodes.iter() {
+                let meta = self.meta_db.get(node).unwrap();
+
+                // like in the text output the same line is only printed once
+                if prev_meta.is_some_and(|pm| pm.loc == meta.loc) && !node.is_sink() {
+                    continue;
+                }
+
+                // name the value only if the C variable is known, temporaries alone mean nothing
+                let value = match meta.var_name {
+                    Some(_) => format!("value {}", meta.var_label().unwrap()),
+                    None => "value".to_string()
+                };
+                let what = if node.is_source() {
+                    let class = node.source_class.map_or(String::new(), |c| format!(" ({})", c.name()));
+                    format!("{} {} originates here{}", node.taint.name(), value, class)
+                } else if node.is_sink() {
+                    format!("the {} reaches the sink here", value)
+                } else {
+                    format!("{} {} flows here", node.taint.name(), value)
+                };
+
+                writeln!(out, "{} note: {} in {}: {}",
+                         GccPrinter::<T>::position(meta),
+                         what,
+                         meta.loc.func,
+                         meta.loc.src_line.as_ref().unwrap_or(&meta.line))?;
+
+                prev_meta = Some(meta);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn position(meta: &TgMetaNode) -> String {
+        match (meta.loc.lineno, meta.loc.column) {
+            (Some(lineno), Some(column)) => format!("{}:{}:{}:", meta.loc.project_path().display(), lineno, column),
+            (Some(lineno), None) => format!("{}:{}:", meta.loc.project_path().display(), lineno),
+            (None, _) => format!("{}:", meta.loc.project_path().display())
+        }
+    }
+}
+
+impl<'a, T: TgMetaDb> Renderer for GccPrinter<'a, T> {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.findings.reset(self.graph);
+
+        for sink in self.graph.sinks.iter() {
+            self.print_traces_of(out, sink)?;
+        }
+
+        Ok(())
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::io::Result;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::Graph;
+use super::TgNode;
+use super::tgnode::Taint;
+use super::meta::TgMetaDb;
+use super::meta::sources;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+use super::render::Trace;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 1em 2em; }
+a { text-decoration: none; }
+table.src { border-collapse: collapse; font-family: monospace; }
+table.src td { padding: 0 0.5em; white-space: pre; }
+table.src td.ln { text-align: right; color: #888; border-right: 1px solid #ccc; }
+tr.red { background: #ffd0d0; }
+tr.blue { background: #d0dcff; }
+tr.green { background: #d0ffd0; }
+tr.sink td { font-weight: bold; }
+tr:target { outline: 2px solid #f0a000; }
+.cols { display: flex; gap: 2em; align-items: flex-start; }
+.trace { min-width: 30em; }
+.trace ol { font-family: monospace; padding-left: 2em; }
+.R { color: #c00000; } .B { color: #0000c0; } .G { color: #008000; }
+";
+
+struct IndexEntry {
+    page: String,
+    kind: &'static str,
+    lineno: Option<usize>,
+    src_line: String,
+    traces: usize
+}
+
+/// Writes a static HTML report with an index of all sinks and one page per
+/// sink that shows its traces next to the source code. The pages do not
+/// need any external assets. The traces suppressed by asb-ignore comments or
+/// the baseline are left out. As a renderer it writes into its directory and
+/// not to the given output.
+pub struct HtmlReport<'a, T: 'a + TgMetaDb> {
+    graph: &'a Graph,
+    meta_db: &'a mut T,
+    findings: Findings,
+    dir: String
+}
+
+impl<'a, T: TgMetaDb> HtmlReport<'a, T> {
+    pub fn new(graph: &'a Graph, meta_db: &'a mut T, dir: &str) -> HtmlReport<'a, T> {
+        HtmlReport {
+            graph,
+            meta_db,
+            findings: Findings::new(),
+            dir: dir.to_string()
+        }
+    }
+
+    fn write(&mut self) -> Result<()> {
+        let dir = self.dir.clone();
+        self.findings.reset(self.graph);
+
+        fs::create_dir_all(&dir)?;
+
+        // file -> function -> sinks
+        let mut index: BTreeMap<String, BTreeMap<String, Vec<IndexEntry>>> = BTreeMap::new();
+        let mut suppressed = 0;
+
+        for sink in self.graph.sinks.iter() {
+            let page = format!("sink_{}.html", sink.idx + 1);
+            let traces = match self.write_sink_page(sink, &Path::new(&dir).join(&page))? {
+                Some(traces) => traces,
+                None => {
+                    suppressed += 1;
+                    continue;
+                }
+            };
+
+            let meta = self.meta_db.get(sink).unwrap();
+            index.entry(meta.loc.file.clone()).or_default()
+                .entry(meta.loc.func.clone()).or_default()
+                .push(IndexEntry {
+                    page,
+                    kind: sink.sink_kind.unwrap().name(),
+                    lineno: meta.loc.lineno,
+                    src_line: meta.loc.src_line.clone().unwrap_or_default(),
+                    traces
+                });
+        }
+
+        let mut f = File::create(Path::new(&dir).join("index.html"))?;
+        HtmlReport::<T>::write_header(&mut f, &format!("tgproc report: {}", self.graph.options.logfile))?;
+        writeln!(f, "<h1>Sinks in {}</h1>", escape(&self.graph.options.logfile))?;
+        writeln!(f, "<p>{} sinks, {} suppressed</p>", self.graph.sinks.len() - suppressed, suppressed)?;
+
+        for (file, funcs) in index.iter() {
+            writeln!(f, "<h2>{}</h2>", escape(file))?;
+            for (func, entries) in funcs.iter() {
+                writeln!(f, "<h3>{}</h3>\n<ul>", escape(func))?;
+                for e in entries.iter() {
+                    writeln!(f, "<li><a href=\"{}\">line {}</a> [{}] <code>{}</code> ({} traces)</li>",
+                             e.page,
+                             e.lineno.map_or("?".to_string(), |l| l.to_string()),
+                             e.kind,
+                             escape(&e.src_line),
+                             e.traces)?;
+                }
+                writeln!(f, "</ul>")?;
+            }
+        }
+
+        writeln!(f, "</body>\n</html>")
+    }
+
+    /// Returns the number of traces of the sink or None if all of them are
+    /// suppressed and there is no page
+    fn write_sink_page(&mut self, sink: &TgNode, path: &Path) -> Result<Option<usize>> {
+        let all_traces = self.findings.traces_of(self.graph, self.meta_db, sink);
+        self.findings.count_all(sink.sink_kind.unwrap(), &all_traces);
+        let traces: Vec<&Trace> = all_traces.iter().filter(|t| t.suppression.is_none()).collect();
+        if traces.is_empty() && !all_traces.is_empty() {
+            return Ok(None);
+        }
+
+        // the files shown next to the traces and where their source was found,
+        // the one with the sink first
+        let mut files: Vec<(String, PathBuf)> = vec![];
+        // the strongest taint per file and line
+        let mut taints: HashMap<(String, usize), &Taint> = HashMap::new();
+
+        for node in Some(&sink).into_iter().chain(traces.iter().flat_map(|t| t.nodes.iter())) {
+            if let Some(meta) = self.meta_db.get(node) {
+                if !files.iter().any(|(file, _)| *file == meta.loc.file) {
+                    files.push((meta.loc.file.clone(), meta.loc.path().to_path_buf()));
+                }
+                if let Some(lineno) = meta.loc.lineno {
+                    let entry = taints.entry((meta.loc.file.clone(), lineno)).or_insert(&node.taint);
+                    if node.taint.strength() > entry.strength() {
+                        *entry = &node.taint;
+                    }
+                }
+            }
+        }
+
+        let sink_meta = self.meta_db.get(sink).unwrap();
+        let title = format!("{} in {} ({}:{})",
+                            sink.sink_kind.unwrap().description(),
+                            sink_meta.loc.func,
+                            sink_meta.loc.file,
+                            sink_meta.loc.lineno.map_or("?".to_string(), |l| l.to_string()));
+
+        let mut f = File::create(path)?;
+        HtmlReport::<T>::write_header(&mut f, &title)?;
+        writeln!(f, "<p><a href=\"index.html\">&larr; all sinks</a></p>")?;
+        writeln!(f, "<h1>{}</h1>", escape(&title))?;
+        writeln!(f, "<div class=\"cols\">\n<div class=\"trace\">")?;
+
+        for (tidx, trace) in traces.iter().enumerate() {
+            match trace.nodes[0].source_class {
+                Some(class) => writeln!(f, "<h2>Trace {} ({} source)</h2>\n<ol>", tidx + 1, class.name())?,
+                None => writeln!(f, "<h2>Trace {}</h2>\n<ol>", tidx + 1)?
+            }
+            for node in trace.nodes.iter() {
+                let abbrv = node.taint.abbrv();
+                match self.meta_db.get(node) {
+                    Some(meta) => {
+                        let fidx = files.iter().position(|(file, _)| *file == meta.loc.file).unwrap();
+                        let text = format!("{}:{} {}",
+                                           meta.loc.func,
+                                           meta.loc.lineno.map_or("?".to_string(), |l| l.to_string()),
+                                           meta.loc.src_line.as_ref().unwrap_or(&meta.line));
+                        match meta.loc.lineno {
+                            Some(lineno) => writeln!(f, "<li class=\"{}\"><a class=\"{}\" href=\"#f{}-L{}\" title=\"{}\">[{}] {}</a></li>",
+                                                     abbrv, abbrv, fidx, lineno, escape(&meta.line), abbrv, escape(&text))?,
+                            None => writeln!(f, "<li class=\"{}\" title=\"{}\">[{}] {}</li>",
+                                             abbrv, escape(&meta.line), abbrv, escape(&text))?
+                        }
+                    },
+                    None => writeln!(f, "<li class=\"{}\">[{}] taintgrind log line {}</li>", abbrv, abbrv, node.idx + 1)?
+                }
+            }
+            writeln!(f, "</ol>")?;
+        }
+
+        writeln!(f, "</div>\n<div class=\"source\">")?;
+
+        let sink_line = sink_meta.loc.lineno;
+        for (fidx, (file, src_path)) in files.iter().enumerate() {
+            writeln!(f, "<h2>{}</h2>", escape(file))?;
+            match sources::lines(src_path) {
+                Some(lines) => {
+                    writeln!(f, "<table class=\"src\">")?;
+                    for (lidx, line) in lines.iter().enumerate() {
+                        let lineno = lidx + 1;
+                        let mut classes = vec![];
+                        if let Some(taint) = taints.get(&(file.clone(), lineno)) {
+                            classes.push(taint.name());
+                        }
+                        if fidx == 0 && sink_line == Some(lineno) {
+                            classes.push("sink");
+                        }
+                        writeln!(f, "<tr id=\"f{}-L{}\" class=\"{}\"><td class=\"ln\">{}</td><td>{}</td></tr>",
+                                 fidx, lineno, classes.join(" "), lineno, escape(line))?;
+                    }
+                    writeln!(f, "</table>")?;
+                },
+                None => writeln!(f, "<p>[file not found]</p>")?
+            }
+        }
+
+        writeln!(f, "</div>\n</div>\n</body>\n</html>")?;
+
+        Ok(Some(traces.len()))
+    }
+
+    fn write_header(f: &mut File, title: &str) -> Result<()> {
+        writeln!(f, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(f, "<title>{}</title>\n<style>{}</style>\n</head>\n<body>", escape(title), STYLE)
+    }
+}
+
+impl<'a, T: TgMetaDb> Renderer for HtmlReport<'a, T> {
+    fn render(&mut self, _out: &mut dyn Write) -> Result<()> {
+        self.write()
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
+
+/// Escape a string for HTML or XML text and attributes
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
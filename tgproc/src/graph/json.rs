@@ -0,0 +1,146 @@
+use serde_json;
+use serde_json::Value;
+use std::io::Result;
+use std::io::Write;
+
+use super::Graph;
+use super::TgNode;
+use super::meta::TgMetaDb;
+use super::meta::callstack;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+use super::render::Suppression;
+use super::render::Trace;
+
+/// Prints all sinks together with their traces as JSON, either as one document
+/// or as JSON Lines with one sink per line. The traces that are known from the
+/// baseline are left out, and so are the sinks that have only known traces;
+/// the sinks with an asb-ignore comment are kept with its reason in "ignored".
+pub struct JsonPrinter<'a, T: 'a + TgMetaDb> {
+    graph: &'a Graph,
+    meta_db: &'a mut T,
+    findings: Findings,
+    json_lines: bool
+}
+
+impl<'a, T: TgMetaDb> JsonPrinter<'a, T> {
+    pub fn new(graph: &'a Graph, meta_db: &'a mut T, json_lines: bool) -> JsonPrinter<'a, T> {
+        JsonPrinter {
+            graph,
+            meta_db,
+            findings: Findings::new(),
+            json_lines
+        }
+    }
+
+    /// None if all traces of the sink are known from the baseline
+    fn sink_to_json(&mut self, sink: &TgNode) -> Option<Value> {
+        let all_traces = self.findings.traces_of(self.graph, self.meta_db, sink);
+        self.findings.count_all(sink.sink_kind.unwrap(), &all_traces);
+        let known = all_traces.iter().filter(|t| matches!(t.suppression, Some(Suppression::Baseline))).count();
+        if known > 0 && known == all_traces.len() {
+            return None;
+        }
+        let traces: Vec<Trace> = all_traces.into_iter().filter(|t| !matches!(t.suppression, Some(Suppression::Baseline))).collect();
+        let kind = sink.sink_kind.unwrap();
+
+        let ignored = self.meta_db.get(sink).and_then(|m| m.loc.asb_ignore_reason());
+        let backtrace = match self.meta_db.get(sink) {
+            Some(meta) => callstack::backtrace(meta, &mut self.findings.debug_db).iter().map(|frame| json!({
+                "func": frame.func,
+                "file": frame.file,
+                "line": frame.lineno,
+                "inlined": frame.inlined,
+                "reconstructed": frame.reconstructed
+            })).collect::<Vec<Value>>(),
+            None => vec![]
+        };
+
+        Some(json!({
+            "kind": kind.name(),
+            "sink": self.node_to_json(sink),
+            "reasons": sink.sink_reasons.iter().map(|r| self.node_to_json(r)).collect::<Vec<Value>>(),
+            "ignored": ignored,
+            "backtrace": backtrace,
+            "traces": traces.iter()
+                .map(|t| t.nodes.iter().map(|n| self.node_to_json(n)).collect::<Vec<Value>>())
+                .collect::<Vec<Vec<Value>>>()
+        }))
+    }
+
+    fn node_to_json(&self, node: &TgNode) -> Value {
+        let mut node_json = json!({
+            "log_line": node.idx + 1,
+            "taint": node.taint.name(),
+            "sink": node.is_sink(),
+            "source": node.is_source(),
+            "source_class": node.source_class.map(|c| c.name())
+        });
+
+        // the meta node might not be available if the node was not kept
+        if let Some(meta) = self.meta_db.get(node) {
+            node_json["var"] = json!(meta.var);
+            node_json["var_name"] = json!(meta.var_name);
+            node_json["call_path"] = json!(meta.stack.as_ref().map(|s| s.path()));
+            node_json["call_path_reconstructed"] = json!(meta.stack.as_ref().is_some_and(|s| s.is_reconstructed()));
+            node_json["loc"] = json!({
+                "addr": format!("0x{:x}", meta.loc.addr),
+                "file": meta.loc.file,
+                "line": meta.loc.lineno,
+                "column": meta.loc.column,
+                "func": meta.loc.func,
+                "inlined_at": meta.loc.inlined_at.iter().map(|site| json!({
+                    "func": site.func,
+                    "file": site.file,
+                    "line": site.lineno,
+                    "column": site.column
+                })).collect::<Vec<Value>>(),
+                "src": meta.loc.src_line
+            });
+            node_json["tg_line"] = json!(meta.line);
+        }
+
+        node_json
+    }
+}
+
+impl<'a, T: TgMetaDb> Renderer for JsonPrinter<'a, T> {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.findings.reset(self.graph);
+
+        let mut sinks = vec![];
+
+        for sink in self.graph.sinks.iter() {
+            let mut sink_json = match self.sink_to_json(sink) {
+                Some(sink_json) => sink_json,
+                None => continue
+            };
+
+            if self.json_lines {
+                sink_json["logfile"] = json!(self.graph.options.logfile);
+                writeln!(out, "{}", serde_json::to_string(&sink_json).unwrap())?;
+            } else {
+                sinks.push(sink_json);
+            }
+        }
+
+        if !self.json_lines {
+            let doc = json!({
+                "logfile": self.graph.options.logfile,
+                "sinks": sinks
+            });
+            writeln!(out, "{}", serde_json::to_string_pretty(&doc).unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use std::io::Result;
+use std::io::Write;
+
+use super::Graph;
+use super::TgNode;
+use super::meta::TgMetaDb;
+use super::meta::TgMetaNode;
+use super::term::Columns;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+use super::render::Suppression;
+use super::html::escape;
+
+struct TestCase {
+    name: String,
+    classname: String,
+    failure: Option<(String, String)>, // message and trace text
+    skipped: Option<String>
+}
+
+/// Prints the findings of a log as a JUnit XML testsuite. Each finding
+/// (identified by its baseline fingerprint) becomes a failing testcase and the
+/// suppressed ones are skipped. Several logs are written as several testsuites
+/// between write_header and write_footer.
+pub struct JunitPrinter<'a, T: 'a + TgMetaDb> {
+    graph: &'a Graph,
+    meta_db: &'a mut T,
+    findings: Findings
+}
+
+impl<'a, T: TgMetaDb> JunitPrinter<'a, T> {
+    pub fn new(graph: &'a Graph, meta_db: &'a mut T) -> JunitPrinter<'a, T> {
+        JunitPrinter {
+            graph,
+            meta_db,
+            findings: Findings::new()
+        }
+    }
+
+    pub fn write_header(out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(out, "<testsuites name=\"tgproc\">")
+    }
+
+    pub fn write_footer(out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "</testsuites>")
+    }
+
+    /// Write the testsuite of the log
+    pub fn write_suite(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.findings.reset(self.graph);
+
+        let mut cases: Vec<TestCase> = vec![];
+        let mut reported: HashSet<String> = HashSet::new();
+
+        for sink in self.graph.sinks.iter() {
+            let kind = sink.sink_kind.unwrap();
+
+            for trace in self.findings.traces_of(self.graph, self.meta_db, sink) {
+                self.findings.count(kind, &trace);
+                let sink_meta = self.meta_db.get(sink).unwrap();
+                let fp = Findings::fingerprint(self.meta_db, sink, trace.nodes[0]);
+
+                if reported.contains(&fp) {
+                    // the same finding was already reported by another trace
+                    continue;
+                }
+
+                let (failure, skipped) = match trace.suppression {
+                    None => (Some((kind.description().to_string(), JunitPrinter::<T>::trace_text(&trace.nodes, self.meta_db))), None),
+                    Some(Suppression::InSource(ref reason)) => (None, Some(format!("asb-ignore: {}", reason))),
+                    Some(Suppression::Baseline) => (None, Some("known finding from the baseline".to_string()))
+                };
+
+                reported.insert(fp.clone());
+                cases.push(TestCase {
+                    name: fp,
+                    classname: format!("{}.{}", sink_meta.loc.file, sink_meta.loc.func),
+                    failure,
+                    skipped
+                });
+            }
+        }
+
+        if cases.is_empty() {
+            // make green runs visible, too
+            cases.push(TestCase {
+                name: "no address sensitive behavior".to_string(),
+                classname: self.graph.options.logfile.clone(),
+                failure: None,
+                skipped: None
+            });
+        }
+
+        let failures = cases.iter().filter(|c| c.failure.is_some()).count();
+        let skipped = cases.iter().filter(|c| c.skipped.is_some()).count();
+
+        writeln!(out, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" errors=\"0\">",
+                 escape(&self.graph.options.logfile), cases.len(), failures, skipped)?;
+        for case in cases.iter() {
+            write!(out, "    <testcase name=\"{}\" classname=\"{}\"", escape(&case.name), escape(&case.classname))?;
+            if let Some((ref message, ref text)) = case.failure {
+                write!(out, ">\n      <failure message=\"{}\" type=\"asb\">{}</failure>\n    </testcase>\n",
+                       escape(message), escape(text))?;
+            } else if let Some(ref reason) = case.skipped {
+                write!(out, ">\n      <skipped message=\"{}\"/>\n    </testcase>\n", escape(reason))?;
+            } else {
+                writeln!(out, "/>")?;
+            }
+        }
+        writeln!(out, "  </testsuite>")
+    }
+
+    /// Write a testsuite for a log that could not be processed
+    pub fn write_error_suite(out: &mut dyn Write, logfile: &str, message: &str) -> Result<()> {
+        write!(out, "  <testsuite name=\"{0}\" tests=\"1\" failures=\"0\" skipped=\"0\" errors=\"1\">\n    \
+                     <testcase name=\"parse\" classname=\"{0}\">\n      \
+                     <error message=\"{1}\"/>\n    \
+                     </testcase>\n  \
+                     </testsuite>\n",
+               escape(logfile), escape(message))
+    }
+
+    fn trace_text(trace: &[&TgNode], meta_db: &T) -> String {
+        let metas: Vec<&TgMetaNode> = trace.iter().map(|node| meta_db.get(node).unwrap()).collect();
+        // like the text output written to a file
+        let cols = Columns::new(&metas, None);
+
+        let mut text = String::new();
+        for (node, meta) in trace.iter().zip(metas.iter()) {
+            text.push_str(&format!("{} {}", node.taint.abbrv(), cols.format(meta)));
+            if let Some(class) = node.source_class {
+                text.push_str(&format!("  ({})", class.name()));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl<'a, T: TgMetaDb> Renderer for JunitPrinter<'a, T> {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        JunitPrinter::<T>::write_header(out)?;
+        self.write_suite(out)?;
+        JunitPrinter::<T>::write_footer(out)
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
@@ -0,0 +1,120 @@
+extern crate glob;
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use self::glob::Pattern;
+
+use super::meta::SrcLoc;
+use super::meta::TgMetaNode;
+
+/// The object paths that are libraries unless configured otherwise
+const DEFAULT_LIB_GLOBS: [&str; 2] = ["obj:*.so", "obj:*.so.*"];
+
+/// What a glob is matched against
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Object, // the binary if the log has no source location
+    Source, // the source file from the log or the debug info and where it was found
+    Func
+}
+
+impl Target {
+    fn name(&self) -> &str {
+        match *self {
+            Target::Object => "obj",
+            Target::Source => "src",
+            Target::Func => "func"
+        }
+    }
+}
+
+struct Rule {
+    target: Target,
+    pattern: Pattern
+}
+
+impl Rule {
+    /// Parse a rule like obj:/usr/lib/*, src:third_party/* or func:std::*
+    fn parse(rule: &str) -> Result<Rule> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidInput, format!("{}: {}", rule, msg));
+
+        let (target, glob) = match rule.find(':') {
+            Some(pos) => (&rule[..pos], &rule[pos+1..]),
+            None => return Err(invalid("expected obj:<glob>, src:<glob> or func:<glob>".to_string()))
+        };
+
+        let target = match target {
+            "obj" => Target::Object,
+            "src" => Target::Source,
+            "func" => Target::Func,
+            _ => return Err(invalid(format!("unknown kind {}, expected obj, src or func", target)))
+        };
+
+        let pattern = Pattern::new(glob).map_err(|e| invalid(e.to_string()))?;
+        Ok(Rule { target, pattern })
+    }
+
+    /// The parts of the location the glob is matched against, none if the
+    /// location has none, e.g. no object path because it has a source file.
+    /// A source file is matched as given by the debug info and as found below
+    /// the source roots, relative to the current directory.
+    fn subjects(&self, meta: &TgMetaNode, src: &SrcLoc) -> Vec<String> {
+        match self.target {
+            Target::Object if meta.loc.lineno.is_none() => vec![meta.loc.file.clone()],
+            Target::Source if src.lineno.is_some() => {
+                let found = src.project_path().to_string_lossy().into_owned();
+                if found == src.file { vec![found] } else { vec![found, src.file.clone()] }
+            },
+            Target::Func => vec![meta.loc.func.clone()],
+            _ => vec![]
+        }
+    }
+
+    /// The description of the match if the glob matches the location
+    fn describe_match(&self, meta: &TgMetaNode, src: &SrcLoc) -> Option<String> {
+        self.subjects(meta, src).into_iter()
+            .find(|s| self.pattern.matches(s))
+            .map(|s| format!("{} {} matches {}", self.target.name(), s, self.pattern.as_str()))
+    }
+}
+
+/// Decides which locations are library code, i.e. not part of the analysed
+/// program. The --own-glob rules are checked first, then the --lib-glob rules;
+/// a location that matches neither is our code. Without --lib-glob the shared
+/// objects (*.so, *.so.*) are libraries.
+pub struct LibClassifier {
+    own: Vec<Rule>,
+    lib: Vec<Rule>
+}
+
+impl LibClassifier {
+    pub fn new(lib_globs: &[String], own_globs: &[String]) -> Result<LibClassifier> {
+        let lib = if lib_globs.is_empty() {
+            DEFAULT_LIB_GLOBS.iter().map(|g| Rule::parse(g)).collect::<Result<Vec<Rule>>>()?
+        } else {
+            lib_globs.iter().map(|g| Rule::parse(g)).collect::<Result<Vec<Rule>>>()?
+        };
+        let own = own_globs.iter().map(|g| Rule::parse(g)).collect::<Result<Vec<Rule>>>()?;
+
+        Ok(LibClassifier { own, lib })
+    }
+
+    /// Whether any src: glob is given, which needs the source file of each
+    /// location to be looked up
+    pub fn needs_sources(&self) -> bool {
+        self.own.iter().chain(self.lib.iter()).any(|r| r.target == Target::Source)
+    }
+
+    /// Whether the location is in a library and why; src is the location
+    /// completed by SrcLoc::complete_info if needs_sources
+    pub fn classify(&self, meta: &TgMetaNode, src: &SrcLoc) -> (bool, String) {
+        if let Some(desc) = self.own.iter().find_map(|r| r.describe_match(meta, src)) {
+            return (false, format!("{} (--own-glob)", desc));
+        }
+        if let Some(desc) = self.lib.iter().find_map(|r| r.describe_match(meta, src)) {
+            return (true, format!("{} (--lib-glob)", desc));
+        }
+        (false, "no glob matches".to_string())
+    }
+}
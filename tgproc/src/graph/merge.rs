@@ -0,0 +1,232 @@
+use super::Graph;
+use super::meta::TgMetaDb;
+use super::meta::TgMetaNode;
+use super::meta::SrcLoc;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+use super::tgnode::SourceClass;
+use super::term;
+use super::term::Columns;
+use ansi_term::Colour;
+use std::collections::HashMap;
+use std::io::Result;
+use std::io::Write;
+use std::path::Path;
+use super::regex::Regex;
+
+/// A finding is identified by the source location of its sink and the
+/// location where the taint originated (i.e. where the address was allocated).
+/// Neither depends on the line numbers of a specific taintgrind log, so the same
+/// finding can be recognized across several runs.
+/// The source is None if findings are only matched by their sink, e.g. because
+/// the allocation sites are not comparable between different builds.
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct FindingKey {
+    pub sink: SrcLoc,
+    pub source: Option<SrcLoc>
+}
+
+struct MergedFinding {
+    sink: TgMetaNode,
+    source: TgMetaNode, // the first source that was seen for this finding
+    source_class: Option<SourceClass>,
+
+    /// the number of traces per run that lead to this finding
+    counts: Vec<usize>
+}
+
+/// Collects the findings of several runs of the same program and correlates them
+pub struct FindingMerger {
+    runs: Vec<String>,
+    merged: HashMap<FindingKey, MergedFinding>,
+    order: Vec<FindingKey>, // the order in which the findings were first seen
+    findings: Findings,
+    match_sources: bool,
+    matrix: bool,
+    color: bool
+}
+
+impl FindingMerger {
+    pub fn new(match_sources: bool, color: bool) -> FindingMerger {
+        FindingMerger {
+            runs: vec![],
+            merged: HashMap::new(),
+            order: vec![],
+            findings: Findings::new(),
+            match_sources,
+            matrix: false,
+            color
+        }
+    }
+
+    /// Render only a matrix of the sinks and the runs they were found in
+    pub fn set_matrix(&mut self, matrix: bool) {
+        self.matrix = matrix;
+    }
+
+    /// Derive a short name for a run from the name of its log, e.g.
+    /// write02.O3.output is named O3
+    pub fn opt_level_of(logfile: &str) -> String {
+        lazy_static! {
+            static ref RE_OPT_LEVEL: Regex = Regex::new(r"\.(O[0-3sz]|Ofast)(\.|$)").unwrap();
+        }
+
+        let name = Path::new(logfile).file_name().and_then(|n| n.to_str()).unwrap_or(logfile);
+        match RE_OPT_LEVEL.captures(name) {
+            Some(cap) => cap.at(1).unwrap().to_string(),
+            None => name.to_string()
+        }
+    }
+
+    pub fn add_run<T: TgMetaDb>(&mut self, name: &str, graph: &Graph, meta_db: &mut T) {
+        let run = self.runs.len();
+        self.runs.push(name.to_string());
+
+        for finding in self.merged.values_mut() {
+            finding.counts.push(0);
+        }
+
+        self.findings.summary.add_graph(graph);
+
+        for sink in graph.sinks.iter() {
+            for trace in graph.get_traces(sink) {
+                let src = trace[0];
+                let snk = trace[trace.len() - 1];
+                self.findings.summary.add_printed(sink.sink_kind.unwrap());
+
+                meta_db.get_mut(src).unwrap().loc.complete_info(&mut self.findings.debug_db);
+                meta_db.get_mut(snk).unwrap().loc.complete_info(&mut self.findings.debug_db);
+
+                let src_meta = meta_db.get(src).unwrap();
+                let snk_meta = meta_db.get(snk).unwrap();
+
+                let key = FindingKey {
+                    sink: snk_meta.loc.clone(),
+                    source: if self.match_sources { Some(src_meta.loc.clone()) } else { None }
+                };
+
+                if !self.merged.contains_key(&key) {
+                    self.order.push(key.clone());
+                    self.merged.insert(key.clone(), MergedFinding {
+                        sink: snk_meta.clone(),
+                        source: src_meta.clone(),
+                        source_class: src.source_class,
+                        counts: vec![0; run + 1]
+                    });
+                }
+
+                self.merged.get_mut(&key).unwrap().counts[run] += 1;
+            }
+        }
+    }
+
+    fn print(&self, out: &mut dyn Write) -> Result<()> {
+        let nameswidth = self.runs.iter().map(|r| r.len()).max().unwrap_or(0);
+        let mut partial = 0;
+
+        let cols = self.columns(true, "source: ".len());
+
+        writeln!(out, "Merged findings of {} runs", self.runs.len())?;
+
+        for key in self.order.iter() {
+            let finding = self.merged.get(key).unwrap();
+            self.print_sep(out, "================================================================================", Colour::Green)?;
+
+            match finding.source_class {
+                Some(class) => writeln!(out, "source: {}  ({})", cols.format(&finding.source), class.name())?,
+                None => writeln!(out, "source: {}", cols.format(&finding.source))?
+            }
+            writeln!(out, "sink:   {}", cols.format(&finding.sink))?;
+
+            for (run, count) in self.runs.iter().zip(finding.counts.iter()) {
+                if *count > 0 {
+                    writeln!(out, "    {:<2$}  {:5}", run, count, nameswidth)?;
+                } else {
+                    writeln!(out, "    {:<2$}  {:>5}", run, "-", nameswidth)?;
+                }
+            }
+
+            let present = finding.counts.iter().filter(|&&c| c > 0).count();
+            if present < self.runs.len() {
+                partial += 1;
+                self.print_sep(out,
+                               &format!(">>>> Only present in {} of {} runs <<<<",
+                                        present,
+                                        self.runs.len()),
+                               Colour::Yellow)?;
+            }
+        }
+
+        self.print_sep(out, "================================================================================", Colour::Green)?;
+        writeln!(out, "{} findings, {} present in only some runs.", self.order.len(), partial)
+    }
+
+    /// Print a matrix that shows which sink was found in which run
+    fn print_matrix(&self, out: &mut dyn Write) -> Result<()> {
+        let colwidth = self.runs.iter().map(|r| r.len()).max().unwrap_or(0).max(3);
+        let cols = self.columns(false, self.runs.len() * (colwidth + 1) + 2);
+
+        for run in self.runs.iter() {
+            write!(out, "{:>1$} ", run, colwidth)?;
+        }
+        writeln!(out)?;
+
+        for key in self.order.iter() {
+            let finding = self.merged.get(key).unwrap();
+
+            for count in finding.counts.iter() {
+                let mark = if *count > 0 { "x" } else { "-" };
+                write!(out, "{:1$}", "", colwidth - 1)?;
+                if self.color {
+                    let clr = if *count > 0 { Colour::Red } else { Colour::Green };
+                    write!(out, "{} ", clr.paint(mark))?;
+                } else {
+                    write!(out, "{} ", mark)?;
+                }
+            }
+
+            writeln!(out, "  {}", cols.format(&finding.sink))?;
+        }
+        Ok(())
+    }
+
+    /// The columns of the text output for the sinks and optionally the sources
+    /// that are printed after indent chars
+    fn columns(&self, sources: bool, indent: usize) -> Columns {
+        let metas: Vec<&TgMetaNode> = self.order.iter()
+            .map(|key| self.merged.get(key).unwrap())
+            .flat_map(|f| Some(&f.sink).into_iter().chain(if sources { Some(&f.source) } else { None }))
+            .collect();
+        let mut cols = Columns::new(&metas, term::width());
+        cols.set_indent(indent);
+        cols
+    }
+
+    fn print_sep(&self, out: &mut dyn Write, sep: &str, color: Colour) -> Result<()> {
+        if self.color {
+            writeln!(out, "{}", color.paint(sep))
+        } else {
+            writeln!(out, "{}", sep)
+        }
+    }
+}
+
+impl Renderer for FindingMerger {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        if self.matrix {
+            self.print_matrix(out)
+        } else {
+            self.print(out)
+        }
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    /// The statistics of all runs
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
@@ -0,0 +1,237 @@
+extern crate object;
+
+use serde_json;
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use self::object::Object;
+use self::object::read::ReadCache;
+
+use super::debuginfo::DebugLoc;
+use super::debuginfo::InlineSite;
+
+pub type AddrMap = HashMap<u64, Option<DebugLoc>>;
+
+/// Keeps the results of the debug info lookups of each binary in a JSON file,
+/// so that reruns on the same binaries don't need to read the debug info again.
+/// The directory is $TGPROC_CACHE_DIR (the cache is disabled if it is set but
+/// empty), $XDG_CACHE_HOME/tgproc or ~/.cache/tgproc. A cache file is only used
+/// if the build-id of the binary (or its mtime if it has none) did not change.
+pub struct DiskCache {
+    dir: PathBuf
+}
+
+impl DiskCache {
+    pub fn new() -> Option<DiskCache> {
+        let dir = match env::var_os("TGPROC_CACHE_DIR") {
+            Some(ref d) if d.is_empty() => return None,
+            Some(d) => PathBuf::from(d),
+            None => match (env::var_os("XDG_CACHE_HOME"), env::var_os("HOME")) {
+                (Some(ref xdg), _) if !xdg.is_empty() => PathBuf::from(xdg).join("tgproc"),
+                (_, Some(home)) => PathBuf::from(home).join(".cache").join("tgproc"),
+                _ => return None
+            }
+        };
+
+        Some(DiskCache { dir })
+    }
+
+    /// The cached locations of the binary or None if there are none or they are outdated
+    pub fn load(&self, binary: &str) -> Option<AddrMap> {
+        let key = DiskCache::key(binary)?;
+        let doc: Value = serde_json::from_slice(&fs::read(self.path(binary)).ok()?).ok()?;
+
+        if doc["binary"] != json!(binary) || doc["key"] != json!(key) {
+            return None;
+        }
+
+        let mut locs = HashMap::new();
+        for (addr, loc) in doc["locs"].as_object()?.iter() {
+            let addr = u64::from_str_radix(addr.trim_start_matches("0x"), 16).ok()?;
+            locs.insert(addr, DiskCache::loc_from_json(loc));
+        }
+        Some(locs)
+    }
+
+    pub fn save(&self, binary: &str, locs: &AddrMap) {
+        let key = match DiskCache::key(binary) {
+            Some(key) => key,
+            None => return
+        };
+
+        let locs_json: serde_json::Map<String, Value> = locs.iter()
+            .map(|(addr, loc)| (format!("0x{:x}", addr), loc.as_ref().map_or(Value::Null, DiskCache::loc_to_json)))
+            .collect();
+        let doc = json!({ "binary": binary, "key": key, "locs": locs_json });
+
+        // write to a temporary file first so that concurrent runs never see half a file
+        let path = self.path(binary);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        if fs::create_dir_all(&self.dir).is_ok() && fs::write(&tmp, serde_json::to_vec(&doc).unwrap()).is_ok() {
+            let _ = fs::rename(&tmp, &path);
+        }
+    }
+
+    fn path(&self, binary: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        binary.hash(&mut hasher);
+        let name = Path::new(binary).file_name().map_or("".into(), |n| n.to_string_lossy());
+        self.dir.join(format!("{}-{:016x}.json", name, hasher.finish()))
+    }
+
+    /// The build-id of the binary or its size and mtime if it has no build-id
+    fn key(binary: &str) -> Option<String> {
+        let file = fs::File::open(binary).ok()?;
+        let meta = file.metadata().ok()?;
+
+        // only the headers and the note sections are read, not the whole binary
+        let data = ReadCache::new(file);
+        if let Ok(Some(id)) = object::File::parse(&data).and_then(|obj| obj.build_id()) {
+            return Some(format!("build-id:{}", id.iter().map(|b| format!("{:02x}", b)).collect::<String>()));
+        }
+
+        let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(format!("mtime:{}.{:09}:{}", mtime.as_secs(), mtime.subsec_nanos(), meta.len()))
+    }
+
+    fn loc_to_json(loc: &DebugLoc) -> Value {
+        json!({
+            "func": loc.func,
+            "file": loc.file,
+            "line": loc.lineno,
+            "column": loc.column,
+            "inlined_at": loc.inlined_at.iter().map(|site| json!({
+                "func": site.func,
+                "file": site.file,
+                "line": site.lineno,
+                "column": site.column
+            })).collect::<Vec<Value>>()
+        })
+    }
+
+    fn loc_from_json(loc: &Value) -> Option<DebugLoc> {
+        let inlined_at = loc["inlined_at"].as_array()?.iter().map(|site| InlineSite {
+            func: site["func"].as_str().unwrap_or("???").to_string(),
+            file: site["file"].as_str().unwrap_or("??").to_string(),
+            lineno: site["line"].as_u64().map(|l| l as usize),
+            column: site["column"].as_u64().map(|c| c as usize)
+        }).collect();
+
+        Some(DebugLoc {
+            func: loc["func"].as_str()?.to_string(),
+            file: loc["file"].as_str()?.to_string(),
+            lineno: loc["line"].as_u64()? as usize,
+            column: loc["column"].as_u64().map(|c| c as usize),
+            inlined_at
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::time::Duration;
+
+    fn cache(name: &str) -> DiskCache {
+        DiskCache { dir: env::temp_dir().join(format!("tgproc-cache-{}-{}", name, process::id())) }
+    }
+
+    fn locs() -> AddrMap {
+        let mut locs = HashMap::new();
+        locs.insert(0x401000, Some(DebugLoc {
+            func: "f".to_string(),
+            file: "a.c".to_string(),
+            lineno: 3,
+            column: Some(5),
+            inlined_at: vec![InlineSite { func: "main".to_string(), file: "a.c".to_string(), lineno: Some(10), column: None }]
+        }));
+        locs.insert(0x401010, None);
+        locs
+    }
+
+    fn assert_same(a: &AddrMap, b: &AddrMap) {
+        assert_eq!(a.len(), b.len());
+        for (addr, loc) in a.iter() {
+            match (loc, &b[addr]) {
+                (Some(x), Some(y)) => {
+                    assert_eq!((&x.func, &x.file, x.lineno, x.column), (&y.func, &y.file, y.lineno, y.column));
+                    assert_eq!(x.inlined_at.len(), y.inlined_at.len());
+                    for (s, t) in x.inlined_at.iter().zip(y.inlined_at.iter()) {
+                        assert_eq!((&s.func, &s.file, s.lineno, s.column), (&t.func, &t.file, t.lineno, t.column));
+                    }
+                },
+                (None, None) => (),
+                _ => panic!("different locations at 0x{:x}", addr)
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let cache = cache("load");
+        let binary = cache.dir.with_extension("bin");
+        fs::write(&binary, b"not an object file").unwrap();
+        let binary = binary.to_str().unwrap();
+
+        assert!(cache.load(binary).is_none());
+        cache.save(binary, &locs());
+        assert_same(&cache.load(binary).unwrap(), &locs());
+
+        fs::remove_dir_all(&cache.dir).unwrap();
+        fs::remove_file(binary).unwrap();
+    }
+
+    #[test]
+    fn changed_mtime() {
+        let cache = cache("mtime");
+        let binary = cache.dir.with_extension("bin");
+        fs::write(&binary, b"not an object file").unwrap();
+
+        cache.save(binary.to_str().unwrap(), &locs());
+        assert!(DiskCache::key(binary.to_str().unwrap()).unwrap().starts_with("mtime:"));
+        assert!(cache.load(binary.to_str().unwrap()).is_some());
+
+        let file = fs::File::options().write(true).open(&binary).unwrap();
+        let mtime = file.metadata().unwrap().modified().unwrap();
+        file.set_modified(mtime + Duration::from_secs(1)).unwrap();
+        assert!(cache.load(binary.to_str().unwrap()).is_none());
+
+        fs::remove_dir_all(&cache.dir).unwrap();
+        fs::remove_file(&binary).unwrap();
+    }
+
+    #[test]
+    fn changed_build_id() {
+        let cache = cache("build-id");
+        let binary = cache.dir.with_extension("bin");
+        fs::copy(env::current_exe().unwrap(), &binary).unwrap();
+
+        // the test binary has a build-id if the linker added one
+        let key = DiskCache::key(binary.to_str().unwrap()).unwrap();
+        if let Some(id) = key.strip_prefix("build-id:") {
+            cache.save(binary.to_str().unwrap(), &locs());
+            assert!(cache.load(binary.to_str().unwrap()).is_some());
+
+            let id: Vec<u8> = (0..id.len()).step_by(2).map(|i| u8::from_str_radix(&id[i..i+2], 16).unwrap()).collect();
+            let mut data = fs::read(&binary).unwrap();
+            let pos = data.windows(id.len()).position(|w| w == &id[..]).unwrap();
+            data[pos] ^= 0xff;
+            fs::write(&binary, &data).unwrap();
+
+            assert_ne!(DiskCache::key(binary.to_str().unwrap()).unwrap(), key);
+            assert!(cache.load(binary.to_str().unwrap()).is_none());
+            fs::remove_dir_all(&cache.dir).unwrap();
+        }
+
+        fs::remove_file(&binary).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use super::DebugInfoDb;
+use super::SrcLoc;
+use super::TgMetaNode;
+
+/// A frame of the call stack at a line of the log. The frames are shared
+/// between all lines, so a line only holds a pointer to its innermost frame.
+pub struct Frame {
+    pub func: String,
+    pub call_site: Option<SrcLoc>, // where the function was called, None for the outermost frame
+    pub reconstructed: bool, // the call was inferred from a change of the function, see CallStack
+    pub parent: Option<Rc<Frame>>
+}
+
+impl Frame {
+    /// Whether any call up to this frame was inferred
+    pub fn is_reconstructed(&self) -> bool {
+        self.reconstructed || self.parent.as_ref().is_some_and(|p| p.is_reconstructed())
+    }
+
+    /// The functions from the outermost to the innermost one
+    pub fn path(&self) -> Vec<&str> {
+        let mut path = vec![self.func.as_str()];
+        let mut frame = self;
+        while let Some(ref parent) = frame.parent {
+            path.push(&parent.func);
+            frame = parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// A frame of a backtrace, the innermost one first
+pub struct BtFrame {
+    pub func: String,
+    pub file: String,
+    pub lineno: Option<usize>,
+    pub inlined: bool, // the function was inlined into the one of the next frame
+    pub reconstructed: bool // the call to the function of the previous frame was inferred, see CallStack
+}
+
+/// Rebuilds the call stack while the log is read. Taintgrind only logs the
+/// lines with taint, so the stack follows the changes of the function:
+///  - a function that is already on the stack was returned to
+///  - any other function was called from the previous line
+///
+/// The log has no call and return lines, so recursion is not recognized and a
+/// function that is called after another one returned without a line with
+/// taint looks like it was called by that one. Therefore every frame that is
+/// pushed here is marked as reconstructed.
+pub struct CallStack {
+    top: Option<Rc<Frame>>,
+    prev_loc: Option<SrcLoc>
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack {
+            top: None,
+            prev_loc: None
+        }
+    }
+
+    /// The innermost frame of the line with the location
+    pub fn step(&mut self, loc: &SrcLoc) -> Rc<Frame> {
+        let top = match self.top.take() {
+            Some(top) if top.func == loc.func => top,
+            Some(top) if CallStack::find(&top, &loc.func).is_some() => {
+                CallStack::find(&top, &loc.func).unwrap()
+            },
+            top => Rc::new(Frame {
+                func: loc.func.clone(),
+                call_site: top.as_ref().and(self.prev_loc.clone()),
+                reconstructed: top.is_some(),
+                parent: top
+            })
+        };
+
+        self.top = Some(top.clone());
+        self.prev_loc = Some(loc.clone());
+        top
+    }
+
+    fn find(frame: &Rc<Frame>, func: &str) -> Option<Rc<Frame>> {
+        let mut frame = frame.clone();
+        loop {
+            if frame.func == func {
+                return Some(frame);
+            }
+            frame = frame.parent.clone()?;
+        }
+    }
+}
+
+/// The backtrace of the node: the inlined functions of its location from the
+/// debug info and the calls of its call stack
+pub fn backtrace(meta: &TgMetaNode, debug_db: &mut DebugInfoDb) -> Vec<BtFrame> {
+    let mut frames = vec![];
+    add_frames(&mut frames, &meta.loc, false);
+
+    let mut frame = meta.stack.clone();
+    while let Some(f) = frame {
+        if let Some(ref call_site) = f.call_site {
+            let mut call_site = call_site.clone();
+            call_site.complete_info(debug_db);
+            add_frames(&mut frames, &call_site, f.reconstructed);
+        }
+        frame = f.parent.clone();
+    }
+
+    frames
+}
+
+/// Add the frame of the location and the ones of the functions inlined there;
+/// reconstructed tells whether the location is an inferred call site
+fn add_frames(frames: &mut Vec<BtFrame>, loc: &SrcLoc, reconstructed: bool) {
+    let (mut func, mut file, mut lineno) = (loc.func.clone(), loc.file.clone(), loc.lineno);
+    for site in loc.inlined_at.iter() {
+        frames.push(BtFrame { func, file, lineno, inlined: true, reconstructed });
+        func = site.func.clone();
+        file = site.file.clone();
+        lineno = site.lineno;
+    }
+    frames.push(BtFrame { func, file, lineno, inlined: false, reconstructed });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(func: &str, lineno: usize) -> SrcLoc {
+        SrcLoc::new_u64(0x400000 + lineno as u64, "a.c".to_string(), Some(lineno), func.to_string())
+    }
+
+    #[test]
+    fn reconstructed_calls() {
+        let mut stack = CallStack::new();
+        let main = stack.step(&loc("main", 1));
+        assert_eq!(main.path(), ["main"]);
+        assert!(!main.is_reconstructed());
+
+        // g is called by main after f returned, but taintgrind did not log
+        // a line of main in between
+        let f = stack.step(&loc("f", 10));
+        assert_eq!(f.path(), ["main", "f"]);
+        assert!(f.reconstructed);
+        let g = stack.step(&loc("g", 20));
+        assert_eq!(g.path(), ["main", "f", "g"]);
+        assert!(g.reconstructed);
+        assert!(g.is_reconstructed());
+
+        let back = stack.step(&loc("main", 2));
+        assert!(Rc::ptr_eq(&back, &main));
+    }
+}
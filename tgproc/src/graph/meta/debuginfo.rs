@@ -0,0 +1,151 @@
+extern crate addr2line;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use self::addr2line::Loader;
+
+use super::cache::AddrMap;
+use super::cache::DiskCache;
+
+/// A call site at which a function was inlined
+#[derive(Clone)]
+pub struct InlineSite {
+    pub func: String, // the function the call was inlined into
+    pub file: String,
+    pub lineno: Option<usize>,
+    pub column: Option<usize>
+}
+
+/// The location of an address according to the DWARF debug info
+#[derive(Clone)]
+pub struct DebugLoc {
+    pub func: String, // the innermost function, i.e. the inlined one if any
+    pub file: String,
+    pub lineno: usize,
+    pub column: Option<usize>,
+    pub inlined_at: Vec<InlineSite> // the innermost call site first
+}
+
+/// Looks up the source locations of addresses in the DWARF debug info of the
+/// binaries. Each binary is read only once and indexed by address, all results
+/// are cached in memory and on disk.
+pub struct DebugInfoDb {
+    loaders: HashMap<String, Option<Loader>>, // None if the binary has no usable debug info
+    locs: HashMap<String, AddrMap>,
+    disk_cache: Option<DiskCache>,
+    dirty: HashSet<String> // the binaries with new results for the disk cache
+}
+
+impl DebugInfoDb {
+    pub fn new() -> DebugInfoDb {
+        DebugInfoDb {
+            loaders: HashMap::new(),
+            locs: HashMap::new(),
+            disk_cache: DiskCache::new(),
+            dirty: HashSet::new()
+        }
+    }
+
+    pub fn addr2srcloc(&mut self, binary: &str, addr: u64) -> &Option<DebugLoc> {
+        if !self.locs.contains_key(binary) {
+            let cached = self.disk_cache.as_ref().and_then(|c| c.load(binary));
+            self.locs.insert(binary.to_string(), cached.unwrap_or_default());
+        }
+
+        let (loaders, dirty) = (&mut self.loaders, &mut self.dirty);
+        self.locs.get_mut(binary).unwrap().entry(addr).or_insert_with(|| {
+            // the debug info is only read if there is an address that is not cached
+            let loader = loaders.entry(binary.to_string())
+                .or_insert_with(|| Loader::new(binary).ok());
+            dirty.insert(binary.to_string());
+            loader.as_ref().and_then(|l| DebugInfoDb::lookup(l, addr))
+        })
+    }
+
+    fn lookup(loader: &Loader, addr: u64) -> Option<DebugLoc> {
+        // the innermost frame is the one of the address itself, the location
+        // of each outer frame is the call site of the frame before it
+        let mut frames = loader.find_frames(addr).ok()?;
+        let mut chain = vec![];
+
+        while let Ok(Some(frame)) = frames.next() {
+            let func = frame.function.as_ref()
+                .and_then(|f| f.demangle().ok().map(|name| name.into_owned()))
+                .unwrap_or_else(|| "???".to_string());
+            let loc = frame.location.map(|l| (l.file.unwrap_or("??").to_string(),
+                                              l.line.map(|n| n as usize),
+                                              l.column.filter(|&c| c > 0).map(|c| c as usize)));
+            chain.push((func, loc));
+        }
+
+        let mut chain = chain.into_iter();
+        let (func, file, lineno, column) = match chain.next() {
+            Some((func, Some((file, Some(lineno), column)))) => (func, file, lineno, column),
+            _ => return None
+        };
+
+        let inlined_at = chain.map(|(func, loc)| {
+            let (file, lineno, column) = loc.unwrap_or(("??".to_string(), None, None));
+            InlineSite { func, file, lineno, column }
+        }).collect();
+
+        Some(DebugLoc { func, file, lineno, column, inlined_at })
+    }
+}
+
+impl Drop for DebugInfoDb {
+    fn drop(&mut self) {
+        if let Some(ref cache) = self.disk_cache {
+            for binary in self.dirty.iter() {
+                cache.save(binary, &self.locs[binary]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate object;
+
+    use super::*;
+    use std::env;
+    use std::fs;
+    use self::object::Object;
+    use self::object::ObjectSymbol;
+
+    #[inline(never)]
+    fn looked_up() -> usize {
+        line!() as usize
+    }
+
+    fn db() -> DebugInfoDb {
+        DebugInfoDb {
+            loaders: HashMap::new(),
+            locs: HashMap::new(),
+            disk_cache: None,
+            dirty: HashSet::new()
+        }
+    }
+
+    #[test]
+    fn addresses_of_the_test_binary() {
+        let exe = env::current_exe().unwrap();
+        let data = fs::read(&exe).unwrap();
+        let obj = object::File::parse(&*data).unwrap();
+        let addr = obj.symbols()
+            .find(|s| s.name().is_ok_and(|n| n.contains("looked_up") && n.contains("debuginfo")))
+            .unwrap()
+            .address();
+
+        let mut db = db();
+        let binary = exe.to_str().unwrap();
+        let loc = db.addr2srcloc(binary, addr).clone().unwrap();
+        assert!(loc.func.ends_with("looked_up"), "{}", loc.func);
+        assert!(loc.file.ends_with("debuginfo.rs"), "{}", loc.file);
+        assert!((looked_up() - 1..=looked_up() + 1).contains(&loc.lineno), "{}", loc.lineno);
+        assert!(db.dirty.contains(binary));
+
+        assert!(db.addr2srcloc(binary, u64::MAX - 1).is_none());
+        assert!(db.addr2srcloc("/nonexistent/binary", addr).is_none());
+    }
+}
@@ -0,0 +1,215 @@
+extern crate object;
+extern crate regex;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::fs::File;
+use std::io::Result;
+use std::ops::Range;
+use self::object::Object;
+use self::object::ObjectSegment;
+use self::regex::Regex;
+
+/// A range of runtime addresses of an object. The bias is the difference
+/// between the runtime address and the address in the object file.
+struct Mapping {
+    path: String,
+    range: Option<Range<u64>>, // None if unknown, i.e. the whole object has the bias
+    bias: u64
+}
+
+/// Translates the runtime addresses of PIE executables and shared objects to
+/// the addresses in the object files that the debug info refers to. The load
+/// map is either read from a /proc/<pid>/maps style file or built from the
+/// messages of `valgrind -v` in the log, e.g.
+///     --1234-- Reading syms from /tmp/a.out
+///     --1234--    svma 0x0000001060, avma 0x0000109060
+pub struct LoadMap {
+    mappings: Vec<Mapping>,
+    reading: Option<String> // the object of the last "Reading syms from"
+}
+
+impl LoadMap {
+    pub fn new() -> LoadMap {
+        LoadMap {
+            mappings: vec![],
+            reading: None
+        }
+    }
+
+    /// Read the mappings of a /proc/<pid>/maps file, e.g.
+    ///     555555554000-555555555000 r-xp 00001000 08:01 1234  /tmp/a.out
+    pub fn load(path: &str) -> Result<LoadMap> {
+        lazy_static! {
+            static ref RE_MAPS: Regex =
+                Regex::new(r"^([0-9a-fA-F]+)-([0-9a-fA-F]+)\s+\S+\s+([0-9a-fA-F]+)\s+\S+\s+\d+\s+(/.+?)\s*$").unwrap();
+        }
+
+        let mut map = LoadMap::new();
+        // the segments (file offset, size, address) of each object
+        let mut segments: HashMap<String, Vec<(u64, u64, u64)>> = HashMap::new();
+
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if let Some(cap) = RE_MAPS.captures(&line) {
+                let start = u64::from_str_radix(cap.at(1).unwrap(), 16).unwrap();
+                let end = u64::from_str_radix(cap.at(2).unwrap(), 16).unwrap();
+                let offset = u64::from_str_radix(cap.at(3).unwrap(), 16).unwrap();
+                let obj = cap.at(4).unwrap().to_string();
+
+                let segs = segments.entry(obj.clone()).or_insert_with(|| LoadMap::read_segments(&obj));
+                let svma = segs.iter()
+                    .find(|&&(seg_off, size, _)| offset >= seg_off && offset < seg_off + size)
+                    .map_or(offset, |&(seg_off, _, addr)| addr + (offset - seg_off));
+
+                map.mappings.push(Mapping { path: obj, range: Some(start..end), bias: start.wrapping_sub(svma) });
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Remember the load address if the line of the log is one of valgrind's
+    /// mapping messages
+    pub fn add_valgrind_msg(&mut self, line: &str) {
+        lazy_static! {
+            static ref RE_READING: Regex = Regex::new(r"^(--|==)\d+(--|==)\s+Reading syms from (.+?)\s*$").unwrap();
+            static ref RE_SVMA: Regex = Regex::new(r"^(--|==)\d+(--|==)\s+svma (0x[0-9a-fA-F]+), avma (0x[0-9a-fA-F]+)").unwrap();
+        }
+
+        if let Some(cap) = RE_READING.captures(line) {
+            self.reading = Some(cap.at(3).unwrap().to_string());
+        } else if let Some(cap) = RE_SVMA.captures(line) {
+            if let Some(obj) = self.reading.take() {
+                let svma = u64::from_str_radix(&cap.at(3).unwrap()[2..], 16).unwrap();
+                let avma = u64::from_str_radix(&cap.at(4).unwrap()[2..], 16).unwrap();
+                let bias = avma.wrapping_sub(svma);
+                // valgrind does not print the size, so it is taken from the segments
+                let segs = LoadMap::read_segments(&obj);
+                let range = segs.iter().map(|&(_, _, addr)| addr.wrapping_add(bias)).min()
+                    .zip(segs.iter().map(|&(_, size, addr)| addr.wrapping_add(bias).wrapping_add(size)).max())
+                    .map(|(start, end)| start..end);
+                self.mappings.push(Mapping { path: obj, range, bias });
+            }
+        }
+    }
+
+    /// The address in the object file for a runtime address in the object
+    pub fn translate(&self, obj: &str, addr: u64) -> Option<u64> {
+        self.mappings.iter()
+            .find(|m| m.path == obj && m.range.as_ref().is_none_or(|r| r.contains(&addr)))
+            .map(|m| addr.wrapping_sub(m.bias))
+    }
+
+    /// The object that is mapped at the runtime address, if known
+    pub fn object_at(&self, addr: u64) -> Option<&str> {
+        self.mappings.iter()
+            .find(|m| m.range.as_ref().is_some_and(|r| r.contains(&addr)))
+            .map(|m| m.path.as_str())
+    }
+
+    fn read_segments(obj: &str) -> Vec<(u64, u64, u64)> {
+        let data = match fs::read(obj) {
+            Ok(data) => data,
+            Err(_) => return vec![]
+        };
+
+        match object::File::parse(&*data) {
+            Ok(file) => file.segments().map(|seg| {
+                let (offset, size) = seg.file_range();
+                (offset, size, seg.address())
+            }).collect(),
+            Err(_) => vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+
+    #[test]
+    fn valgrind_msgs() {
+        let mut map = LoadMap::new();
+        map.add_valgrind_msg("--1234-- Reading syms from /nonexistent/a.out");
+        map.add_valgrind_msg("--1234--    svma 0x0000001060, avma 0x0000109060");
+        map.add_valgrind_msg("==1234== Reading syms from /nonexistent/libc.so.6");
+        map.add_valgrind_msg("==1234==    svma 0x0000028000, avma 0x0004a28000");
+
+        assert_eq!(map.translate("/nonexistent/a.out", 0x109070), Some(0x1070));
+        assert_eq!(map.translate("/nonexistent/libc.so.6", 0x4a28100), Some(0x28100));
+        assert_eq!(map.translate("/tmp/b.out", 0x109070), None);
+        // without the objects the ranges are unknown
+        assert_eq!(map.object_at(0x109070), None);
+    }
+
+    #[test]
+    fn valgrind_msgs_object_ranges() {
+        // the test binary itself is an object whose segments can be read
+        let exe = env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap();
+        let segs = LoadMap::read_segments(exe);
+        let first = segs.iter().map(|&(_, _, addr)| addr).min().unwrap();
+        let last = segs.iter().map(|&(_, size, addr)| addr + size).max().unwrap();
+
+        let mut map = LoadMap::new();
+        map.add_valgrind_msg(&format!("--1234-- Reading syms from {}", exe));
+        map.add_valgrind_msg(&format!("--1234--    svma 0x{:010x}, avma 0x{:010x}", first, first + 0x4000000));
+
+        assert_eq!(map.object_at(first + 0x4000000), Some(exe));
+        assert_eq!(map.object_at(last + 0x4000000 - 1), Some(exe));
+        assert_eq!(map.object_at(last + 0x4000000), None);
+        assert_eq!(map.translate(exe, first + 0x4000010), Some(first + 0x10));
+    }
+
+    #[test]
+    fn valgrind_msgs_svma_needs_object() {
+        let mut map = LoadMap::new();
+        map.add_valgrind_msg("--1234--    svma 0x0000001060, avma 0x0000109060");
+        map.add_valgrind_msg("--1234-- Reading syms from /nonexistent/a.out");
+        map.add_valgrind_msg("0x109070: main (a.c:3)");
+        map.add_valgrind_msg("--1234--    svma 0x0000001060, avma 0x0000109060");
+        map.add_valgrind_msg("--1234--    svma 0x0000002000, avma 0x0000200000");
+
+        assert_eq!(map.mappings.len(), 1);
+        assert_eq!(map.translate("/nonexistent/a.out", 0x109070), Some(0x1070));
+    }
+
+    #[test]
+    fn maps_file() {
+        // the objects do not exist, so their offsets are taken as addresses
+        let path = env::temp_dir().join(format!("tgproc-loadmap-{}.maps", process::id()));
+        File::create(&path).unwrap().write_all(b"\
+555555554000-555555555000 r--p 00000000 08:01 1234                       /nonexistent/a.out
+555555555000-555555556000 r-xp 00001000 08:01 1234                       /nonexistent/a.out
+555555557000-555555578000 rw-p 00000000 00:00 0                          [heap]
+7ffff7dd3000-7ffff7dd5000 rw-p 00000000 00:00 0 
+7ffff7fc3000-7ffff7fc7000 r-xp 00002000 08:01 5678                       /nonexistent/lib a.so
+").unwrap();
+
+        let map = LoadMap::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let map = map.unwrap();
+
+        assert_eq!(map.mappings.len(), 3);
+        assert_eq!(map.translate("/nonexistent/a.out", 0x555555554010), Some(0x10));
+        assert_eq!(map.translate("/nonexistent/a.out", 0x555555555100), Some(0x1100));
+        assert_eq!(map.translate("/nonexistent/a.out", 0x555555556000), None);
+        assert_eq!(map.translate("/nonexistent/lib a.so", 0x7ffff7fc3010), Some(0x2010));
+        assert_eq!(map.translate("[heap]", 0x555555557000), None);
+
+        assert_eq!(map.object_at(0x555555555100), Some("/nonexistent/a.out"));
+        assert_eq!(map.object_at(0x7ffff7fc3010), Some("/nonexistent/lib a.so"));
+        assert_eq!(map.object_at(0x555555557000), None);
+    }
+
+    #[test]
+    fn maps_file_missing() {
+        assert!(LoadMap::load("/nonexistent/maps").is_err());
+    }
+}
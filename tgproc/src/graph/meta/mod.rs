@@ -1,82 +1,61 @@
 extern crate regex;
-extern crate walkdir;
 
 mod simple;
+mod debuginfo;
+mod cache;
+pub mod symbol;
+mod loadmap;
+pub mod sources;
+mod varnames;
+pub mod callstack;
 
-use std::u64;
 use std::result::Result;
 use std::num::ParseIntError;
-use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
 use std::env;
-use std::io::BufReader;
-use std::io::BufRead;
-use std::fs::File;
-use std::process::Command;
-use std::fmt;
-use std::fmt::Display;
-use std::fmt::Formatter;
+use std::hash::Hash;
+use std::hash::Hasher;
 use self::regex::Regex;
-use self::walkdir::WalkDir;
 
 use super::tgnode::TgNode;
 
-pub struct DebugInfoDb(HashMap<String, HashMap<u64, Option<(String, usize)>>>);
-
-impl DebugInfoDb {
-    pub fn new() -> DebugInfoDb {
-        DebugInfoDb(HashMap::new())
-    }
-    
-    fn addr2srcloc(&mut self, binary: &str, addr: u64) -> &Option<(String, usize)> {
-        let DebugInfoDb(ref mut bin_map) = *self;
-        
-        if ! bin_map.contains_key(binary) {
-            bin_map.insert(binary.to_string(), HashMap::new());
-        }
-
-        let mut addr_map = bin_map.get_mut(binary).unwrap();
-
-        if ! addr_map.contains_key(&addr) {
-            let output = Command::new("addr2line")
-                .arg("-e")
-                .arg(binary)
-                .arg(format!("0x{:x}", addr))
-                .output()
-                .unwrap_or_else(|e| { panic!("failed to execute process: {}", e) });
-
-            let output_str = String::from_utf8(output.stdout).unwrap();
-            let mut out_split = output_str.split(":");
-            let file = out_split.next().unwrap().to_string();
-            
-            if let Ok(lineno) = out_split.next().unwrap().trim().parse::<usize>() {
-                addr_map.insert(addr, Some((file, lineno)));
-            } else {
-                addr_map.insert(addr, None);
-            }
-        }
-
-        addr_map.get(&addr).unwrap()
-    }
-}
+pub use self::debuginfo::DebugInfoDb;
+pub use self::debuginfo::InlineSite;
+pub use self::loadmap::LoadMap;
+pub use self::varnames::VarNames;
+pub use self::callstack::CallStack;
+use self::callstack::Frame;
+use self::symbol::Place;
 
+#[derive(Clone)]
 pub struct SrcLoc {
-    pub addr: u64,
-    pub file: String,
+    pub addr: u64, // the runtime address
+    pub obj_addr: u64, // the address in the object file, differs from addr for PIE and shared objects
+    pub file: String, // as given by the log or the debug info, identifies the location
+    pub src_path: Option<PathBuf>, // where the source file was found, see sources::find
     pub lineno: Option<usize>,
+    pub column: Option<usize>, // only known from the debug info
+    pub inlined_at: Vec<InlineSite>,
     pub src_line: Option<String>,
-    pub func: String
+    pub func: String,
+    pub full_func: Option<String> // the function with its template arguments if func was simplified
 }
 
 impl SrcLoc {
     pub fn new_u64(addr: u64, file: String, lineno: Option<usize>, func: String) -> SrcLoc {
         SrcLoc {
-            addr: addr,
-            file: file,
-            lineno: lineno,
+            addr,
+            obj_addr: addr,
+            file,
+            src_path: None,
+            lineno,
+            column: None,
+            inlined_at: vec![],
             src_line: None,
-            func: func
+            func,
+            full_func: None
         }
     }
 
@@ -84,75 +63,46 @@ impl SrcLoc {
         u64::from_str_radix(&addr[2..], 16).map(|a| SrcLoc::new_u64(a, file, lineno, func))
     }
 
-    #[allow(unused_parens)]
     pub fn complete_info(&mut self, debug_db: &mut DebugInfoDb) {
         if self.lineno.is_none() {
-            if let Some((ref file, lineno)) = *debug_db.addr2srcloc(&self.file, self.addr) {
-                self.file = file.clone();
-                self.lineno = Some(lineno);
+            if let Some(ref dloc) = *debug_db.addr2srcloc(&self.file, self.obj_addr) {
+                self.file = dloc.file.clone();
+                self.lineno = Some(dloc.lineno);
+                self.column = dloc.column;
+                self.inlined_at = dloc.inlined_at.clone();
+                if !self.inlined_at.is_empty() {
+                    // the function in the log is the one the code was inlined into
+                    self.func = dloc.func.clone();
+                    self.full_func = None;
+                }
             }
         }
 
-        let mut src_line = None;
-        let mut file = None;
-
-        {
-            let filepath = Path::new(&self.file);
-            if ! filepath.exists() {
-                let basename = filepath.file_name().unwrap();
-
-                for entry in WalkDir::new(".").into_iter().filter_map(|e| e.ok()) {
-                    if (entry.path().to_str().map_or(false, |s| s.ends_with(&self.file)) &&
-                        entry.file_name() == basename) {
-                            if let Some(line) = self.load_src_line_from(entry.path()) {
-                                src_line = Some(line);
-                                file = Some(entry.path().to_str().unwrap().to_string());
-                                break;
-                            }
-                    }
-                }
-            } else {
-                src_line = self.load_src_line_from(filepath);
+        if let Some(path) = sources::find(&self.file) {
+            if let Some(line) = self.load_src_line_from(&path) {
+                self.src_line = Some(line);
             }
+            self.src_path = Some(path);
         }
+    }
 
-        if src_line.is_some() {
-            self.src_line = src_line;
-        }
-        if let Some(f) = file {
-            self.file = f;
-        }
+    /// The path of the source file if it was found, the file of the location
+    /// otherwise
+    pub fn path(&self) -> &Path {
+        self.src_path.as_deref().unwrap_or_else(|| Path::new(&self.file))
     }
 
     fn load_src_line_from(&self, path: &Path) -> Option<String> {
-        if let Some(lineno) = self.lineno {
-            if let Ok(f) = File::open(path) {
-                let reader = BufReader::new(&f);
-                if let Some(Ok(line)) = reader.lines().nth(lineno-1) {
-                    return Some(line.trim().to_string())
-                }
-            }
-        }
-        None
+        self.lineno.and_then(|lineno| SrcLoc::load_line(path, lineno))
     }
-}
 
-impl PartialEq for SrcLoc {
-    fn eq(&self, other: &SrcLoc) -> bool {
-        self.lineno == other.lineno && self.file == other.file && self.func == other.func
+    fn load_line(path: &Path, lineno: usize) -> Option<String> {
+        sources::line(path, lineno).map(|line| line.trim().to_string())
     }
-}
-
-pub struct TgMetaNode {
-    pub line: String,
-    pub loc: SrcLoc,
-}
-
-impl Display for TgMetaNode {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let line = if let Some(ref l) = self.loc.src_line { l.as_str() } else { "[file not found]" };
 
-        let path = Path::new(&self.loc.file);
+    /// The path of the file relative to the current directory
+    pub fn rel_path(&self) -> PathBuf {
+        let path = self.path();
         let mut relpath = path.to_path_buf();
         
         if path.is_absolute() {
@@ -177,44 +127,112 @@ impl Display for TgMetaNode {
             }
         }
 
-        let lineno = self.loc.lineno.unwrap_or(0);
-        write!(f, "{:>29}:{:04}: {:>20}:  {}", relpath.display(), lineno, self.loc.func, line)
+        relpath
     }
-}
 
-impl TgMetaNode {
-    pub fn new(line : String, loc_part: &str) -> TgMetaNode {
+    /// The path of the file relative to the current directory if it is located
+    /// below it, the absolute path otherwise
+    pub fn project_path(&self) -> PathBuf {
+        let path = self.path();
+
+        if let Ok(cwd) = env::current_dir() {
+            let abspath = if path.is_absolute() { path.to_path_buf() } else { cwd.join(path) };
+            let abspath = abspath.canonicalize().unwrap_or(abspath);
+
+            match abspath.strip_prefix(cwd.canonicalize().unwrap_or(cwd)) {
+                Ok(suffix) => suffix.to_path_buf(),
+                Err(_) => abspath
+            }
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// Check whether the findings at this location are suppressed by a comment
+    /// like /* asb-ignore: reason */ or // asb-ignore: reason on the source line
+    /// or the line above it; the comment has to start with the marker.
+    /// complete_info must have been called before.
+    ///
+    /// Returns the reason of the suppression
+    pub fn asb_ignore_reason(&self) -> Option<String> {
         lazy_static! {
-            // e.g. 0x40080D: main (two-taints.c:10)
-            static ref RE_LOC1: Regex = Regex::new(r"(0x\w+): (.+?) \((.+):(\d+)\)").unwrap();
-            
-            // e.g. 0x40080D: main (in /tmp/a.out)
-            static ref RE_LOC2: Regex = Regex::new(r"(0x\w+): (.+?) \(in (.+)\)").unwrap();
+            // the marker has to start a comment; the code before it may contain
+            // string literals, but no comment
+            static ref RE_ASB_IGNORE: Regex = Regex::new(
+                r#"^(?:[^"/]|"(?:[^"\\]|\\.)*"|/[^/*])*(?://+|/\*+)!?\s*asb-ignore\b:?\s*(.*?)\s*(?:\*/|$)"#).unwrap();
         }
-        
-        if let Some(cap) = RE_LOC1.captures(loc_part) {
-            TgMetaNode {
-                line: line,
-                loc: SrcLoc::new(cap.at(1).unwrap(),
-                                 cap.at(3).unwrap().to_string(),
-                                 Some(cap.at(4).unwrap().parse::<usize>().unwrap()),
-                                 cap.at(2).unwrap().to_string()).unwrap(),
-            }
-        } else if let Some(cap) = RE_LOC2.captures(loc_part) {
-            TgMetaNode {
-                line: line,
-                loc: SrcLoc::new(cap.at(1).unwrap(),
-                                 cap.at(3).unwrap().to_string(),
-                                 None,
-                                 cap.at(2).unwrap().to_string()).unwrap(),
+
+        let above = self.lineno.and_then(|lineno| SrcLoc::load_line(self.path(), lineno.saturating_sub(1)));
+
+        for line in self.src_line.iter().chain(above.iter()) {
+            if let Some(cap) = RE_ASB_IGNORE.captures(line) {
+                return Some(cap.at(1).unwrap().to_string());
             }
-        } else {
-            panic!("Could not parse loc part: {}", loc_part);
+        }
+        None
+    }
+}
+
+impl PartialEq for SrcLoc {
+    fn eq(&self, other: &SrcLoc) -> bool {
+        self.lineno == other.lineno && self.file == other.file && self.func == other.func
+    }
+}
+
+impl Eq for SrcLoc {}
+
+impl Hash for SrcLoc {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.lineno.hash(state);
+        self.file.hash(state);
+        self.func.hash(state);
+    }
+}
+
+#[derive(Clone)]
+pub struct TgMetaNode {
+    pub line: String,
+    pub loc: SrcLoc,
+    pub var: Option<String>, // the variable that is defined in this line if any
+    pub var_name: Option<String>, // the name of the C variable the value belongs to if known
+    pub stack: Option<Rc<Frame>> // the innermost frame of the call stack at this line
+}
+
+impl TgMetaNode {
+    pub fn new(line : String, loc_part: &str) -> Result<TgMetaNode, String> {
+        // e.g. 0x40080D: main (two-taints.c:10) or 0x40080D: main (in /tmp/a.out)
+        let loc = match symbol::split_loc(loc_part) {
+            Some((addr, func, Place::Source(file, lineno))) => SrcLoc::new(addr, file, lineno, symbol::demangle(func)),
+            Some((addr, func, Place::Object(obj))) => SrcLoc::new(addr, obj, None, symbol::demangle(func)),
+            None => return Err(format!("Could not parse loc part: {}", loc_part))
+        };
+
+        loc.map(|l| TgMetaNode { line, loc: l, var: None, var_name: None, stack: None })
+            .map_err(|e| format!("Could not parse address in loc part {}: {}", loc_part, e))
+    }
+
+    /// The defined variable with the name of the C variable if it is known,
+    /// e.g. ptr (t54_1741)
+    pub fn var_label(&self) -> Option<String> {
+        match (&self.var, &self.var_name) {
+            (Some(var), Some(name)) => Some(format!("{} ({})", name, var)),
+            (Some(var), None) => Some(var.clone()),
+            (None, _) => None
         }
     }
 
-    pub fn is_lib(&self) -> bool {
-        self.loc.file.ends_with(".so") // TODO only works on linux
+    /// The line of the log with the defined variable labeled with its C name
+    pub fn named_line(&self) -> String {
+        match (&self.var, &self.var_label(), self.line.rfind(" | ")) {
+            (Some(var), Some(label), Some(pos)) if self.var_name.is_some() => {
+                let (head, flow) = self.line.split_at(pos + 3);
+                match flow.strip_prefix(var.as_str()) {
+                    Some(rest) => format!("{}{}{}", head, label, rest),
+                    None => self.line.clone()
+                }
+            },
+            _ => self.line.clone()
+        }
     }
 }
 
@@ -239,3 +257,34 @@ pub trait TgMetaDb {
 }
 
 pub type SimpleMetaDB = simple::MetaDB;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(line: &str) -> Option<String> {
+        let mut loc = SrcLoc::new_u64(0x400000, "a.c".to_string(), None, "main".to_string());
+        loc.src_line = Some(line.to_string());
+        loc.asb_ignore_reason()
+    }
+
+    #[test]
+    fn asb_ignore_comments() {
+        assert_eq!(reason("*p = 0; // asb-ignore: checked above"), Some("checked above".to_string()));
+        assert_eq!(reason("*p = 0; /* asb-ignore checked above */"), Some("checked above".to_string()));
+        assert_eq!(reason("  //asb-ignore"), Some("".to_string()));
+        assert_eq!(reason("/** asb-ignore: doc */"), Some("doc".to_string()));
+        assert_eq!(reason("puts(\"a // b\"); x = a / b; // asb-ignore: ok"), Some("ok".to_string()));
+    }
+
+    #[test]
+    fn asb_ignore_rejected() {
+        assert_eq!(reason("*p = 0;"), None);
+        assert_eq!(reason("*p = 0; // no-asb-ignore"), None);
+        assert_eq!(reason("*p = 0; // see asb-ignore below"), None);
+        assert_eq!(reason("*p = 0; // asb-ignored"), None);
+        assert_eq!(reason("puts(\"// asb-ignore\");"), None);
+        assert_eq!(reason("puts(\"a \\\" // asb-ignore\");"), None);
+        assert_eq!(reason("int asb_ignore = asb - ignore;"), None);
+    }
+}
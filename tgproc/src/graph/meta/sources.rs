@@ -0,0 +1,136 @@
+extern crate walkdir;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use self::walkdir::WalkDir;
+
+static SOURCES: OnceLock<SourceIndex> = OnceLock::new();
+
+thread_local! {
+    // the lines of each source file read so far, None if it cannot be read
+    static LINES: RefCell<HashMap<PathBuf, Option<Rc<Vec<String>>>>> = RefCell::new(HashMap::new());
+}
+
+/// Finds the source files named in the debug info. A file is looked up in this
+/// order, the first hit wins:
+///  1. the path after applying the first matching --path-map prefix rewrite
+///  2. that path relative to each --source-root, in the order given
+///  3. the files below the source roots (default: the current directory)
+///     with the same name; the one whose path has the longest common suffix
+///     with the wanted path wins, ties are broken by the order of the roots
+///     and then alphabetically
+pub struct SourceIndex {
+    roots: Vec<PathBuf>,
+    path_map: Vec<(String, String)>,
+    files: OnceLock<HashMap<OsString, Vec<PathBuf>>> // by file name, built on the first miss
+}
+
+/// Set the source roots and path maps for this run; this can only be done once
+pub fn configure(roots: &[String], path_map: Vec<(String, String)>) {
+    let roots = if roots.is_empty() { vec![PathBuf::from(".")] } else { roots.iter().map(PathBuf::from).collect() };
+    let _ = SOURCES.set(SourceIndex { roots, path_map, files: OnceLock::new() });
+}
+
+/// The path of the source file or None if it cannot be found
+pub fn find(file: &str) -> Option<PathBuf> {
+    index().find(file)
+}
+
+/// The file after applying the first matching --path-map prefix rewrite
+pub fn remap(file: &str) -> String {
+    index().remap(file)
+}
+
+fn index() -> &'static SourceIndex {
+    SOURCES.get_or_init(|| SourceIndex {
+        roots: vec![PathBuf::from(".")],
+        path_map: vec![],
+        files: OnceLock::new()
+    })
+}
+
+/// The lines of the file; each file is only read once per run
+pub fn lines(path: &Path) -> Option<Rc<Vec<String>>> {
+    LINES.with(|files| {
+        files.borrow_mut().entry(path.to_path_buf()).or_insert_with(|| {
+            fs::read(path).ok().map(|data| {
+                Rc::new(String::from_utf8_lossy(&data).lines().map(|l| l.to_string()).collect())
+            })
+        }).clone()
+    })
+}
+
+/// The line of the file with the number (starting at 1)
+pub fn line(path: &Path, lineno: usize) -> Option<String> {
+    if lineno == 0 {
+        return None;
+    }
+    lines(path).and_then(|lines| lines.get(lineno - 1).cloned())
+}
+
+impl SourceIndex {
+    fn find(&self, file: &str) -> Option<PathBuf> {
+        let mapped = self.remap(file);
+        let path = Path::new(&mapped);
+
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+
+        if path.is_relative() {
+            for root in self.roots.iter() {
+                let candidate = root.join(path);
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        let candidates = self.files().get(path.file_name()?)?;
+        // max_by_key returns the last maximum, so search in reverse to prefer the first one
+        candidates.iter().rev()
+            .max_by_key(|c| SourceIndex::common_suffix(c, path))
+            .cloned()
+    }
+
+    fn remap(&self, file: &str) -> String {
+        for (old, new) in self.path_map.iter() {
+            if let Some(rest) = file.strip_prefix(old.as_str()) {
+                return format!("{}{}", new, rest);
+            }
+        }
+        file.to_string()
+    }
+
+    fn files(&self) -> &HashMap<OsString, Vec<PathBuf>> {
+        self.files.get_or_init(|| {
+            let mut files: HashMap<OsString, Vec<PathBuf>> = HashMap::new();
+
+            for root in self.roots.iter() {
+                let mut below_root: Vec<PathBuf> = WalkDir::new(root).into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file())
+                    .map(|e| e.path().to_path_buf())
+                    .collect();
+                below_root.sort();
+
+                for path in below_root {
+                    files.entry(path.file_name().unwrap().to_os_string()).or_default().push(path);
+                }
+            }
+
+            files
+        })
+    }
+
+    /// The number of trailing path components that are the same
+    fn common_suffix(a: &Path, b: &Path) -> usize {
+        a.components().rev().zip(b.components().rev()).take_while(|&(x, y)| x == y).count()
+    }
+}
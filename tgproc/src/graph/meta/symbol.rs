@@ -0,0 +1,216 @@
+extern crate cpp_demangle;
+extern crate rustc_demangle;
+
+/// Where the code of a location is, as given in parentheses by taintgrind
+#[derive(Debug, PartialEq)]
+pub enum Place {
+    Source(String, Option<usize>), // file and line
+    Object(String) // the binary, e.g. "in /tmp/a.out"
+}
+
+/// Split a location like
+///     0x40080D: main (two-taints.c:10)
+///     0x40080D: std::function<void (int)>::operator()(int) const (in /tmp/a.out)
+/// into address, function and place. The place is the last parenthesized group,
+/// so that parentheses and brackets in C++ names don't confuse the parser.
+pub fn split_loc(loc_part: &str) -> Option<(&str, &str, Place)> {
+    let loc_part = loc_part.trim();
+    let colon = loc_part.find(": ")?;
+    let addr = &loc_part[..colon];
+    if !addr.starts_with("0x") {
+        return None;
+    }
+
+    let rest = &loc_part[colon+2..];
+    if !rest.ends_with(')') {
+        return None;
+    }
+
+    // search the opening parenthesis of the last group
+    let mut depth = 0;
+    let mut open = None;
+    for (idx, c) in rest.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => {
+                depth -= 1;
+                if depth == 0 {
+                    open = Some(idx);
+                    break;
+                }
+            },
+            _ => ()
+        }
+    }
+
+    let open = open?;
+    let func = rest[..open].trim_end();
+    let inner = &rest[open+1..rest.len()-1];
+
+    let place = if let Some(obj) = inner.strip_prefix("in ") {
+        Place::Object(obj.to_string())
+    } else {
+        match inner.rfind(':') {
+            Some(pos) => match inner[pos+1..].parse::<usize>() {
+                Ok(lineno) => Place::Source(inner[..pos].to_string(), Some(lineno)),
+                Err(_) => Place::Source(inner.to_string(), None)
+            },
+            None => Place::Source(inner.to_string(), None)
+        }
+    };
+
+    Some((addr, func, place))
+}
+
+/// Demangle Rust (legacy and v0) and Itanium C++ symbols; other names are
+/// returned as they are
+pub fn demangle(name: &str) -> String {
+    if let Ok(sym) = rustc_demangle::try_demangle(name) {
+        // the alternate format omits the hash of legacy symbols
+        return format!("{:#}", sym);
+    }
+
+    if name.starts_with("_Z") {
+        if let Ok(sym) = cpp_demangle::Symbol::new(name) {
+            if let Ok(demangled) = sym.demangle(&cpp_demangle::DemangleOptions::default()) {
+                return demangled;
+            }
+        }
+    }
+
+    name.to_string()
+}
+
+fn is_operator_keyword(prefix: &str) -> bool {
+    prefix.strip_suffix("operator")
+        .is_some_and(|before| !before.ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+}
+
+/// Remove the template arguments from a name, e.g.
+///     std::vector<int, std::allocator<int> >::push_back(int const&)
+/// becomes std::vector::push_back(int const&). Operators like operator<< are kept.
+pub fn simplify(name: &str) -> String {
+    let mut simple = String::new();
+    let mut depth = 0;
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if depth == 0 && is_operator_keyword(&simple) {
+            // copy the operator itself, e.g. <, <<=, ->, <=>
+            let mut c = c;
+            while "<>=-".contains(c) {
+                simple.push(c);
+                match chars.peek() {
+                    Some(&next) if "<>=".contains(next) => c = chars.next().unwrap(),
+                    _ => break
+                }
+            }
+            if !"<>=-".contains(c) {
+                simple.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '<' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            _ if depth == 0 => simple.push(c),
+            _ => ()
+        }
+    }
+
+    simple
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(file: &str, lineno: Option<usize>) -> Place {
+        Place::Source(file.to_string(), lineno)
+    }
+
+    #[test]
+    fn split_loc_source() {
+        assert_eq!(split_loc("0x40080D: main (two-taints.c:10)"),
+                   Some(("0x40080D", "main", source("two-taints.c", Some(10)))));
+        assert_eq!(split_loc("  0x40080D: main (src/a.c:7)  "),
+                   Some(("0x40080D", "main", source("src/a.c", Some(7)))));
+    }
+
+    #[test]
+    fn split_loc_object() {
+        assert_eq!(split_loc("0x4005F0: _Exit (in /usr/lib/libc.so.6)"),
+                   Some(("0x4005F0", "_Exit", Place::Object("/usr/lib/libc.so.6".to_string()))));
+    }
+
+    #[test]
+    fn split_loc_without_line() {
+        assert_eq!(split_loc("0x1: main (a.c)"), Some(("0x1", "main", source("a.c", None))));
+        assert_eq!(split_loc("0x1: main (a.c:x)"), Some(("0x1", "main", source("a.c:x", None))));
+    }
+
+    #[test]
+    fn split_loc_nested_parentheses() {
+        assert_eq!(split_loc("0x400510: std::function<void (int)>::operator()(int) const (in /tmp/a.out)"),
+                   Some(("0x400510",
+                         "std::function<void (int)>::operator()(int) const",
+                         Place::Object("/tmp/a.out".to_string()))));
+        assert_eq!(split_loc("0x401136: main::{lambda(int)#1}::operator()(int) const (l.cc:7)"),
+                   Some(("0x401136", "main::{lambda(int)#1}::operator()(int) const", source("l.cc", Some(7)))));
+    }
+
+    #[test]
+    fn split_loc_operators() {
+        assert_eq!(split_loc("0x400504: std::vector<int, std::allocator<int> >::operator<<=(int) (s.c:4)"),
+                   Some(("0x400504",
+                         "std::vector<int, std::allocator<int> >::operator<<=(int)",
+                         source("s.c", Some(4)))));
+        assert_eq!(split_loc("0x400504: a::operator>(a const&) (s.cc:4)"),
+                   Some(("0x400504", "a::operator>(a const&)", source("s.cc", Some(4)))));
+    }
+
+    #[test]
+    fn split_loc_invalid() {
+        assert_eq!(split_loc("main (s.c:4)"), None);
+        assert_eq!(split_loc("400504: main (s.c:4)"), None);
+        assert_eq!(split_loc("0x400504: main"), None);
+        assert_eq!(split_loc("0x400504: main s.c:4)"), None);
+    }
+
+    #[test]
+    fn simplify_templates() {
+        assert_eq!(simplify("std::vector<int, std::allocator<int> >::push_back(int const&)"),
+                   "std::vector::push_back(int const&)");
+        assert_eq!(simplify("std::function<void (int)>::operator()(int) const"),
+                   "std::function::operator()(int) const");
+        assert_eq!(simplify("my_operator<int>(int)"), "my_operator(int)");
+        assert_eq!(simplify("main"), "main");
+    }
+
+    #[test]
+    fn simplify_operators() {
+        assert_eq!(simplify("foo<int>::operator<<=(int)"), "foo::operator<<=(int)");
+        assert_eq!(simplify("foo<int>::operator<(foo<int> const&)"), "foo::operator<(foo const&)");
+        assert_eq!(simplify("a::operator<=>(a const&)"), "a::operator<=>(a const&)");
+        assert_eq!(simplify("bar<int>::operator->()"), "bar::operator->()");
+        assert_eq!(simplify("std::map<int, int>::operator[](int const&)"), "std::map::operator[](int const&)");
+        assert_eq!(simplify("std::ostream& std::operator<< <std::char_traits<char> >(std::ostream&, char const*)"),
+                   "std::ostream& std::operator<< (std::ostream&, char const*)");
+    }
+
+    #[test]
+    fn simplify_lambdas() {
+        assert_eq!(simplify("main::{lambda(int)#1}::operator()(int) const"),
+                   "main::{lambda(int)#1}::operator()(int) const");
+        assert_eq!(simplify("std::vector<int>::emplace_back<main::{lambda()#1}>(main::{lambda()#1}&&)"),
+                   "std::vector::emplace_back(main::{lambda()#1}&&)");
+    }
+
+    #[test]
+    fn demangle_symbols() {
+        assert_eq!(demangle("_ZN3foo3barEv"), "foo::bar()");
+        assert_eq!(demangle("_ZN3foo3bar17h05af221e174051e9E"), "foo::bar");
+        assert_eq!(demangle("main"), "main");
+    }
+}
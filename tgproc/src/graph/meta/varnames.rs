@@ -0,0 +1,679 @@
+extern crate addr2line;
+extern crate object;
+extern crate regex;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use self::addr2line::gimli;
+use self::object::Object;
+use self::object::ObjectSection;
+use self::object::SectionKind;
+use self::regex::Regex;
+
+use super::LoadMap;
+use super::TgMetaNode;
+use super::super::tgnode::SourceClass;
+
+type Reader<'a> = gimli::EndianSlice<'a, gimli::RunTimeEndian>;
+
+/// The DWARF numbers of the amd64 registers by their offset in the VEX guest
+/// state, i.e. the number in GET:I64 <offset>
+const AMD64_REGS: [(u64, u16); 16] = [
+    (16, 0), (24, 2), (32, 1), (40, 3), (48, 7), (56, 6), (64, 4), (72, 5), // rax rcx rdx rbx rsp rbp rsi rdi
+    (80, 8), (88, 9), (96, 10), (104, 11), (112, 12), (120, 13), (128, 14), (136, 15) // r8 - r15
+];
+const DWARF_RBP: u16 = 6;
+const DWARF_RSP: u16 = 7;
+
+/// How far the stack may reach below the stack pointer of a frame
+const MAX_STACK_SIZE: u64 = 8 << 20;
+/// Where the main stack is if no stack pointer is known, i.e. where valgrind
+/// puts it on amd64 (older and newer versions) and where Linux does natively
+const STACK_RANGES: [Range<u64>; 3] = [0xff0000000..0x1000000000, 0x1ff0000000..0x2000000000, 0x7ff000000000..0x800000000000];
+
+/// Where a variable lives
+#[derive(Clone, Copy)]
+enum VarLoc {
+    Reg(u16),
+    FrameOffset(i64), // relative to the frame base of the function
+    Addr(u64)
+}
+
+#[derive(Clone, Copy)]
+enum FrameBase {
+    Cfa,
+    Reg(u16, i64),
+    Unknown
+}
+
+struct Local {
+    name: String,
+    size: u64,
+    locs: Vec<(Option<Range<u64>>, VarLoc)> // without a range the location is valid in the whole function
+}
+
+/// A function or an inlined function with its locals and parameters
+struct Func {
+    ranges: Vec<Range<u64>>,
+    frame_base: FrameBase,
+    locals: Vec<Local>
+}
+
+/// The variables of a binary according to its DWARF debug info and its
+/// data sections
+struct Vars {
+    funcs: Vec<Func>,
+    globals: Vec<(Range<u64>, String)>, // sorted by address
+    data: Vec<Range<u64>>, // the data sections
+    func_ranges: Vec<(Range<u64>, usize)>, // the ranges of all funcs sorted by start
+    max_end: Vec<u64> // the largest end of the ranges up to each index of func_ranges
+}
+
+/// Maps the registers and memory addresses in the taint flow back to the names
+/// of the C variables, so that a temporary like t54_1741 that was loaded from
+/// the local `ptr` can be shown as `ptr (t54_1741)`. A variable is found if
+///  - taintgrind already printed its name (e.g. ptr:0xffefffd28)
+///  - it is a global or static variable at the address
+///  - it is a local on the stack and the frame base register (rbp) of the
+///    function was read earlier in the log
+///  - it is a local that lives in the register at the code address
+///
+/// The binary is the object in the location of the line, the one that the
+/// load map has at the address of the line or else the one of the "Command:"
+/// line of valgrind.
+pub struct VarNames {
+    binaries: HashMap<String, Option<Vars>>, // None if the binary cannot be read
+    command: Option<String>,
+    regs: HashMap<u16, u64> // the last value of each register read in the log
+}
+
+impl VarNames {
+    pub fn new() -> VarNames {
+        VarNames {
+            binaries: HashMap::new(),
+            command: None,
+            regs: HashMap::new()
+        }
+    }
+
+    /// Remember the program if the line of the log is valgrind's
+    ///     ==1234== Command: ./a.out args
+    pub fn add_valgrind_msg(&mut self, line: &str) {
+        lazy_static! {
+            static ref RE_COMMAND: Regex = Regex::new(r"^==\d+==\s+Command: (\S+)").unwrap();
+        }
+
+        if let Some(cap) = RE_COMMAND.captures(line) {
+            self.command = Some(cap.at(1).unwrap().to_string());
+        }
+    }
+
+    /// The name of the variable that is defined in a line of the log; cmd,
+    /// value and tnt_flow are the columns of the line
+    pub fn name_of(&mut self,
+                   meta: &TgMetaNode,
+                   load_map: &LoadMap,
+                   cmd: &str,
+                   value: &str,
+                   tnt_flow: &str) -> Option<String> {
+        lazy_static! {
+            static ref RE_GET: Regex = Regex::new(r"GET:\w+ (\d+)").unwrap();
+        }
+
+        let var = meta.var.as_ref()?;
+        let pc = meta.loc.obj_addr;
+        let binary = self.binary(meta, load_map);
+
+        if let Some(cap) = RE_GET.captures(cmd) {
+            let offset = cap.at(1).unwrap().parse::<u64>().unwrap();
+            let reg = AMD64_REGS.iter().find(|&&(off, _)| off == offset).map(|&(_, reg)| reg)?;
+            if let Ok(v) = u64::from_str_radix(value.trim_start_matches("0x"), 16) {
+                self.regs.insert(reg, v);
+            }
+            return self.reg_name(&binary?, pc, reg);
+        }
+
+        // a store defines the address, a load has the address as its only source
+        let addr = if VarNames::is_addr(var) {
+            var
+        } else {
+            let sources: Vec<&str> = tnt_flow.split("; ")
+                .filter_map(|flow| flow.split(" <- ").nth(1))
+                .flat_map(|srcs| srcs.split(", "))
+                .collect();
+            match sources[..] {
+                [src] if VarNames::is_addr(src) => src,
+                _ => return None
+            }
+        };
+
+        VarNames::named_token(addr).or_else(|| self.addr_name(&binary?, pc, load_map, addr))
+    }
+
+    /// Whether the address in the value column of a line is on the stack or
+    /// in a global variable. The stack is recognized by the stack registers
+    /// read earlier in the log, the globals by the data sections of the binary.
+    pub fn classify_addr(&mut self, meta: &TgMetaNode, load_map: &LoadMap, value: &str) -> SourceClass {
+        let addr = match u64::from_str_radix(value.trim_start_matches("0x"), 16) {
+            Ok(addr) if addr != 0 => addr,
+            _ => return SourceClass::Unknown
+        };
+
+        let sps: Vec<u64> = [DWARF_RSP, DWARF_RBP].iter().filter_map(|r| self.regs.get(r).cloned()).collect();
+        let on_stack = if sps.is_empty() {
+            STACK_RANGES.iter().any(|r| r.contains(&addr))
+        } else {
+            sps.iter().any(|&sp| addr <= sp.saturating_add(MAX_STACK_SIZE) && addr >= sp.saturating_sub(MAX_STACK_SIZE))
+        };
+        if on_stack {
+            return SourceClass::Stack;
+        }
+
+        // the global may be in another object than the code
+        let binary = load_map.object_at(addr).map(|obj| obj.to_string()).or_else(|| self.binary(meta, load_map));
+        if let Some(binary) = binary {
+            let obj_addr = load_map.translate(&binary, addr).unwrap_or(addr);
+            let vars = self.binaries.entry(binary.clone()).or_insert_with(|| Vars::load(&binary));
+            if vars.as_ref().is_some_and(|v| v.data.iter().any(|r| r.contains(&obj_addr))) {
+                return SourceClass::Global;
+            }
+        }
+
+        SourceClass::Unknown
+    }
+
+    /// The object with the code of the line
+    fn binary(&self, meta: &TgMetaNode, load_map: &LoadMap) -> Option<String> {
+        if meta.loc.lineno.is_none() {
+            // without a source location the location names the object
+            Some(meta.loc.file.clone())
+        } else {
+            load_map.object_at(meta.loc.addr).map(|obj| obj.to_string()).or_else(|| self.command.clone())
+        }
+    }
+
+    /// e.g. 0xffefffd28 or ptr:0xffefffd28
+    fn is_addr(token: &str) -> bool {
+        token.rsplit(':').next().is_some_and(|a| a.starts_with("0x"))
+    }
+
+    /// The name that taintgrind printed in front of the address, if any
+    fn named_token(token: &str) -> Option<String> {
+        match token.rfind(':') {
+            Some(pos) if token[pos+1..].starts_with("0x") => Some(token[..pos].to_string()),
+            _ => None
+        }
+    }
+
+    fn addr_name(&mut self, binary: &str, pc: u64, load_map: &LoadMap, token: &str) -> Option<String> {
+        let addr = u64::from_str_radix(token.trim_start_matches("0x"), 16).ok()?;
+        let regs = &self.regs;
+        let vars = self.binaries.entry(binary.to_string()).or_insert_with(|| Vars::load(binary)).as_ref()?;
+
+        // the globals are in the object file, so PIE addresses must be translated
+        let obj_addr = load_map.translate(binary, addr).unwrap_or(addr);
+        let idx = vars.globals.partition_point(|(r, _)| r.start <= obj_addr);
+        if idx > 0 && vars.globals[idx-1].0.contains(&obj_addr) {
+            return Some(vars.globals[idx-1].1.clone());
+        }
+
+        for fidx in vars.funcs_at(pc) {
+            let func = &vars.funcs[fidx];
+            let frame_base = match func.frame_base {
+                FrameBase::Reg(reg, off) => regs.get(&reg).map(|&v| v.wrapping_add(off as u64)),
+                // after the prologue the CFA is rbp + 16 on amd64
+                FrameBase::Cfa => regs.get(&DWARF_RBP).map(|&v| v.wrapping_add(16)),
+                FrameBase::Unknown => None
+            };
+
+            for local in func.locals.iter() {
+                for &(ref range, loc) in local.locs.iter() {
+                    if range.as_ref().is_some_and(|r| !r.contains(&pc)) {
+                        continue;
+                    }
+                    // static locals are in the object file like the globals
+                    let (value, start) = match (loc, frame_base) {
+                        (VarLoc::FrameOffset(off), Some(fb)) => (addr, fb.wrapping_add(off as u64)),
+                        (VarLoc::Addr(a), _) => (obj_addr, a),
+                        _ => continue
+                    };
+                    if value >= start && value < start.wrapping_add(local.size.max(1)) {
+                        return Some(local.name.clone());
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn reg_name(&mut self, binary: &str, pc: u64, reg: u16) -> Option<String> {
+        let vars = self.binaries.entry(binary.to_string()).or_insert_with(|| Vars::load(binary)).as_ref()?;
+
+        for fidx in vars.funcs_at(pc) {
+            for local in vars.funcs[fidx].locals.iter() {
+                let in_reg = local.locs.iter().any(|&(ref range, loc)| {
+                    matches!(loc, VarLoc::Reg(r) if r == reg) && range.as_ref().is_none_or(|r| r.contains(&pc))
+                });
+                if in_reg {
+                    return Some(local.name.clone());
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Vars {
+    fn load(binary: &str) -> Option<Vars> {
+        let data = fs::read(binary).ok()?;
+        let obj = object::File::parse(&*data).ok()?;
+        let endian = if obj.is_little_endian() { gimli::RunTimeEndian::Little } else { gimli::RunTimeEndian::Big };
+
+        let load_section = |id: gimli::SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+            Ok(obj.section_by_name(id.name())
+                .and_then(|s| s.uncompressed_data().ok())
+                .unwrap_or(Cow::Borrowed(&[])))
+        };
+        let sections = gimli::DwarfSections::load(load_section).ok()?;
+        let dwarf = sections.borrow(|s| gimli::EndianSlice::new(s, endian));
+
+        let data_sections = obj.sections()
+            .filter(|s| matches!(s.kind(), SectionKind::Data | SectionKind::UninitializedData | SectionKind::ReadOnlyData))
+            .map(|s| s.address()..s.address() + s.size())
+            .collect();
+
+        let mut vars = Vars::new(data_sections);
+        let mut units = dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            if let Ok(unit) = dwarf.unit(header) {
+                vars.add_unit(unit.unit_ref(&dwarf));
+            }
+        }
+
+        vars.index();
+        Some(vars)
+    }
+
+    fn new(data: Vec<Range<u64>>) -> Vars {
+        Vars { funcs: vec![], globals: vec![], data, func_ranges: vec![], max_end: vec![] }
+    }
+
+    /// Sort the globals and the ranges of the funcs for the lookups by address
+    fn index(&mut self) {
+        self.globals.sort_by_key(|g| g.0.start);
+
+        self.func_ranges = self.funcs.iter().enumerate()
+            .flat_map(|(idx, f)| f.ranges.iter().map(move |r| (r.clone(), idx)))
+            .collect();
+        self.func_ranges.sort_by_key(|&(ref r, idx)| (r.start, idx));
+
+        let mut max_end = 0;
+        self.max_end = self.func_ranges.iter().map(|(r, _)| { max_end = max_end.max(r.end); max_end }).collect();
+    }
+
+    fn add_unit<'a>(&mut self, unit: gimli::UnitRef<Reader<'a>>) {
+        // the funcs that enclose the current entry with their depth
+        let mut func_stack: Vec<(isize, usize)> = vec![];
+        let mut depth = 0;
+        let mut entries = unit.entries();
+
+        while let Ok(Some((delta, entry))) = entries.next_dfs() {
+            depth += delta;
+            while func_stack.last().is_some_and(|&(d, _)| d >= depth) {
+                func_stack.pop();
+            }
+
+            match entry.tag() {
+                gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
+                    let mut ranges = vec![];
+                    if let Ok(mut iter) = unit.die_ranges(entry) {
+                        while let Ok(Some(r)) = iter.next() {
+                            ranges.push(r.begin..r.end);
+                        }
+                    }
+
+                    // inlined functions use the frame of the function they are inlined into
+                    let frame_base = match entry.attr_value(gimli::DW_AT_frame_base) {
+                        Ok(Some(gimli::AttributeValue::Exprloc(expr))) => Vars::frame_base(unit.encoding(), expr),
+                        _ => func_stack.last().map_or(FrameBase::Unknown, |&(_, f)| self.funcs[f].frame_base)
+                    };
+
+                    self.funcs.push(Func { ranges, frame_base, locals: vec![] });
+                    func_stack.push((depth, self.funcs.len() - 1));
+                },
+                gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter => {
+                    let name = match Vars::name(unit, entry) {
+                        Some(name) => name,
+                        None => continue
+                    };
+                    let size = Vars::type_size(unit, entry).unwrap_or(1);
+                    let locs = Vars::locations(unit, entry);
+
+                    match func_stack.last() {
+                        Some(&(_, f)) => self.funcs[f].locals.push(Local { name, size, locs }),
+                        None => for (_, loc) in locs {
+                            if let VarLoc::Addr(addr) = loc {
+                                self.globals.push((addr..addr + size.max(1), name.clone()));
+                            }
+                        }
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+
+    /// The name of the entry or the one of its abstract origin
+    fn name<'a>(unit: gimli::UnitRef<Reader<'a>>, entry: &gimli::DebuggingInformationEntry<Reader<'a>>) -> Option<String> {
+        if let Ok(Some(name)) = entry.attr_value(gimli::DW_AT_name) {
+            return unit.attr_string(name).ok().map(|s| s.to_string_lossy().into_owned());
+        }
+        match entry.attr_value(gimli::DW_AT_abstract_origin) {
+            Ok(Some(gimli::AttributeValue::UnitRef(offset))) => {
+                let origin = unit.entry(offset).ok()?;
+                let name = origin.attr_value(gimli::DW_AT_name).ok()??;
+                unit.attr_string(name).ok().map(|s| s.to_string_lossy().into_owned())
+            },
+            _ => None
+        }
+    }
+
+    /// The size of the type of the entry; arrays are counted as a whole
+    fn type_size<'a>(unit: gimli::UnitRef<Reader<'a>>, entry: &gimli::DebuggingInformationEntry<Reader<'a>>) -> Option<u64> {
+        let mut offset = match entry.attr_value(gimli::DW_AT_type).ok()?? {
+            gimli::AttributeValue::UnitRef(offset) => offset,
+            _ => return None
+        };
+
+        // follow typedefs and qualifiers
+        for _ in 0..16 {
+            let ty = unit.entry(offset).ok()?;
+            if let Some(size) = ty.attr_value(gimli::DW_AT_byte_size).ok()?.and_then(|s| s.udata_value()) {
+                return Some(size);
+            }
+
+            if ty.tag() == gimli::DW_TAG_array_type {
+                let elem_size = Vars::type_size(unit, &ty)?;
+                let mut count = 1;
+                let mut tree = unit.entries_tree(Some(offset)).ok()?;
+                let mut children = tree.root().ok()?.children();
+                while let Ok(Some(child)) = children.next() {
+                    let sub = child.entry();
+                    if let Some(n) = sub.attr_value(gimli::DW_AT_count).ok().flatten().and_then(|c| c.udata_value()) {
+                        count *= n;
+                    } else if let Some(upper) = sub.attr_value(gimli::DW_AT_upper_bound).ok().flatten().and_then(|u| u.udata_value()) {
+                        count *= upper + 1;
+                    }
+                }
+                return Some(elem_size * count);
+            }
+
+            offset = match ty.attr_value(gimli::DW_AT_type).ok()?? {
+                gimli::AttributeValue::UnitRef(next) => next,
+                _ => return None
+            };
+        }
+        None
+    }
+
+    fn locations<'a>(unit: gimli::UnitRef<Reader<'a>>, entry: &gimli::DebuggingInformationEntry<Reader<'a>>) -> Vec<(Option<Range<u64>>, VarLoc)> {
+        let attr = match entry.attr_value(gimli::DW_AT_location) {
+            Ok(Some(attr)) => attr,
+            _ => return vec![]
+        };
+
+        if let gimli::AttributeValue::Exprloc(expr) = attr {
+            return Vars::var_loc(unit.encoding(), expr).map(|loc| (None, loc)).into_iter().collect();
+        }
+
+        let mut locs = vec![];
+        if let Ok(Some(mut iter)) = unit.attr_locations(attr) {
+            while let Ok(Some(entry)) = iter.next() {
+                if let Some(loc) = Vars::var_loc(unit.encoding(), entry.data) {
+                    locs.push((Some(entry.range.begin..entry.range.end), loc));
+                }
+            }
+        }
+        locs
+    }
+
+    /// Only the simple location expressions of a single operation are supported
+    fn var_loc(encoding: gimli::Encoding, expr: gimli::Expression<Reader>) -> Option<VarLoc> {
+        let mut ops = expr.operations(encoding);
+        let loc = match ops.next().ok()?? {
+            gimli::Operation::Register { register } => VarLoc::Reg(register.0),
+            gimli::Operation::FrameOffset { offset } => VarLoc::FrameOffset(offset),
+            gimli::Operation::Address { address } => VarLoc::Addr(address),
+            _ => return None
+        };
+        match ops.next() {
+            Ok(None) => Some(loc),
+            _ => None
+        }
+    }
+
+    fn frame_base(encoding: gimli::Encoding, expr: gimli::Expression<Reader>) -> FrameBase {
+        match expr.operations(encoding).next() {
+            Ok(Some(gimli::Operation::CallFrameCFA)) => FrameBase::Cfa,
+            Ok(Some(gimli::Operation::Register { register })) => FrameBase::Reg(register.0, 0),
+            Ok(Some(gimli::Operation::RegisterOffset { register, offset, .. })) => FrameBase::Reg(register.0, offset),
+            _ => FrameBase::Unknown
+        }
+    }
+
+    /// The funcs that contain the address, the innermost (inlined) one first.
+    /// The ranges that start at or before the address are searched backwards
+    /// until no earlier range reaches the address anymore.
+    fn funcs_at(&self, pc: u64) -> Vec<usize> {
+        let end = self.func_ranges.partition_point(|(r, _)| r.start <= pc);
+        let mut funcs: Vec<usize> = (0..end).rev()
+            .take_while(|&i| self.max_end[i] > pc)
+            .filter(|&i| self.func_ranges[i].0.contains(&pc))
+            .map(|i| self.func_ranges[i].1)
+            .collect();
+        // inlined functions come after the function they are inlined into
+        funcs.sort_unstable_by(|a, b| b.cmp(a));
+        funcs.dedup();
+        funcs
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+
+    const BIN: &str = "/nonexistent/a.out";
+    const RBP: u64 = 0xffefff000;
+
+    fn expr(bytes: &[u8]) -> gimli::Expression<Reader<'_>> {
+        gimli::Expression(gimli::EndianSlice::new(bytes, gimli::RunTimeEndian::Little))
+    }
+
+    fn encoding() -> gimli::Encoding {
+        gimli::Encoding { address_size: 8, format: gimli::Format::Dwarf32, version: 4 }
+    }
+
+    fn local(name: &str, size: u64, locs: Vec<(Option<Range<u64>>, VarLoc)>) -> Local {
+        Local { name: name.to_string(), size, locs }
+    }
+
+    /// main at 0x1000 with f inlined at 0x1040, g at 0x2000 and the data
+    /// section at 0x4000
+    fn var_names() -> VarNames {
+        let mut vars = Vars::new(vec![0x4000..0x5000]);
+        vars.funcs.push(Func {
+            ranges: vec![0x1000..0x1100],
+            frame_base: FrameBase::Reg(DWARF_RBP, 16),
+            locals: vec![
+                local("buf", 16, vec![(None, VarLoc::FrameOffset(-32))]),
+                local("n", 4, vec![(Some(0x1010..0x1020), VarLoc::Reg(3)), (Some(0x1020..0x1030), VarLoc::FrameOffset(-40))]),
+                local("count", 4, vec![(None, VarLoc::Addr(0x4010))])
+            ]
+        });
+        vars.funcs.push(Func {
+            ranges: vec![0x1040..0x1060],
+            frame_base: FrameBase::Reg(DWARF_RBP, 16),
+            locals: vec![local("i", 4, vec![(None, VarLoc::Reg(3))])]
+        });
+        vars.funcs.push(Func {
+            ranges: vec![0x2000..0x2010, 0x3000..0x3010],
+            frame_base: FrameBase::Cfa,
+            locals: vec![local("p", 8, vec![(None, VarLoc::FrameOffset(-24))])]
+        });
+        vars.globals.push((0x4020..0x4028, "g".to_string()));
+        vars.globals.push((0x4000..0x4008, "first".to_string()));
+        vars.index();
+
+        let mut names = VarNames::new();
+        names.binaries.insert(BIN.to_string(), Some(vars));
+        names.regs.insert(DWARF_RBP, RBP);
+        names
+    }
+
+    fn vars(names: &VarNames) -> &Vars {
+        names.binaries[BIN].as_ref().unwrap()
+    }
+
+    #[test]
+    fn location_expressions() {
+        let enc = encoding();
+        assert!(matches!(Vars::var_loc(enc, expr(&[0x53])), Some(VarLoc::Reg(3)))); // DW_OP_reg3
+        assert!(matches!(Vars::var_loc(enc, expr(&[0x91, 0x70])), Some(VarLoc::FrameOffset(-16)))); // DW_OP_fbreg -16
+        assert!(matches!(Vars::var_loc(enc, expr(&[0x03, 0x10, 0x40, 0, 0, 0, 0, 0, 0])), Some(VarLoc::Addr(0x4010)))); // DW_OP_addr
+        assert!(Vars::var_loc(enc, expr(&[0x91, 0x70, 0x06])).is_none()); // DW_OP_fbreg -16; DW_OP_deref
+        assert!(Vars::var_loc(enc, expr(&[])).is_none());
+    }
+
+    #[test]
+    fn frame_base_expressions() {
+        let enc = encoding();
+        assert!(matches!(Vars::frame_base(enc, expr(&[0x9c])), FrameBase::Cfa)); // DW_OP_call_frame_cfa
+        assert!(matches!(Vars::frame_base(enc, expr(&[0x56])), FrameBase::Reg(6, 0))); // DW_OP_reg6
+        assert!(matches!(Vars::frame_base(enc, expr(&[0x77, 0x10])), FrameBase::Reg(7, 16))); // DW_OP_breg7 16
+        assert!(matches!(Vars::frame_base(enc, expr(&[0x76, 0x70])), FrameBase::Reg(6, -16))); // DW_OP_breg6 -16
+        assert!(matches!(Vars::frame_base(enc, expr(&[0x96])), FrameBase::Unknown)); // DW_OP_nop
+    }
+
+    #[test]
+    fn funcs_at_addresses() {
+        let names = var_names();
+        let vars = vars(&names);
+        assert_eq!(vars.funcs_at(0x1000), [0]);
+        assert_eq!(vars.funcs_at(0x1050), [1, 0]);
+        assert_eq!(vars.funcs_at(0x1060), [0]);
+        assert_eq!(vars.funcs_at(0x3008), [2]);
+        assert!(vars.funcs_at(0x2010).is_empty());
+        assert!(vars.funcs_at(0x0fff).is_empty());
+        assert!(vars.funcs_at(0x1100).is_empty());
+    }
+
+    #[test]
+    fn location_lists() {
+        let mut names = var_names();
+        assert_eq!(names.reg_name(BIN, 0x1015, 3), Some("n".to_string()));
+        assert_eq!(names.reg_name(BIN, 0x1025, 3), None);
+        assert_eq!(names.reg_name(BIN, 0x1015, 4), None);
+        // the inlined function is searched first
+        assert_eq!(names.reg_name(BIN, 0x1050, 3), Some("i".to_string()));
+
+        // n moves to the stack at 0x1020
+        let n_addr = format!("0x{:x}", RBP + 16 - 40);
+        assert_eq!(names.addr_name(BIN, 0x1025, &LoadMap::new(), &n_addr), Some("n".to_string()));
+        assert_eq!(names.addr_name(BIN, 0x1015, &LoadMap::new(), &n_addr), None);
+    }
+
+    #[test]
+    fn register_offsets() {
+        let mut names = var_names();
+        let load_map = LoadMap::new();
+        // buf is at rbp + 16 - 32 with 16 bytes
+        assert_eq!(names.addr_name(BIN, 0x1000, &load_map, &format!("0x{:x}", RBP - 16)), Some("buf".to_string()));
+        assert_eq!(names.addr_name(BIN, 0x1000, &load_map, &format!("0x{:x}", RBP - 1)), Some("buf".to_string()));
+        assert_eq!(names.addr_name(BIN, 0x1000, &load_map, &format!("0x{:x}", RBP)), None);
+        // the CFA is rbp + 16
+        assert_eq!(names.addr_name(BIN, 0x2000, &load_map, &format!("0x{:x}", RBP - 8)), Some("p".to_string()));
+
+        // without the frame base register the locals on the stack are unknown
+        names.regs.clear();
+        assert_eq!(names.addr_name(BIN, 0x1000, &load_map, &format!("0x{:x}", RBP - 16)), None);
+    }
+
+    #[test]
+    fn binary_of_line() {
+        use std::env;
+        use std::fs::File;
+        use std::io::Write;
+        use std::process;
+
+        let path = env::temp_dir().join(format!("tgproc-varnames-{}.maps", process::id()));
+        File::create(&path).unwrap()
+            .write_all(b"7ffff7fc3000-7ffff7fc7000 r-xp 00000000 08:01 5678 /nonexistent/libfoo.so\n").unwrap();
+        let load_map = LoadMap::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let load_map = load_map.unwrap();
+
+        let mut names = VarNames::new();
+        names.add_valgrind_msg("==1234== Command: ./a.out arg");
+        let meta = |loc: &str| TgMetaNode::new(String::new(), loc).unwrap();
+
+        assert_eq!(names.binary(&meta("0x7FFFF7FC3010: foo (foo.c:3)"), &load_map), Some("/nonexistent/libfoo.so".to_string()));
+        assert_eq!(names.binary(&meta("0x401000: main (a.c:3)"), &load_map), Some("./a.out".to_string()));
+        assert_eq!(names.binary(&meta("0x4F1000: bar (in /usr/lib/libbar.so)"), &load_map), Some("/usr/lib/libbar.so".to_string()));
+    }
+
+    #[test]
+    fn classify_addrs() {
+        let mut names = var_names();
+        let meta = TgMetaNode::new(String::new(), &format!("0x2000: g (in {})", BIN)).unwrap();
+        let load_map = LoadMap::new();
+
+        // near the frame registers
+        assert!(matches!(names.classify_addr(&meta, &load_map, &format!("0x{:x}", RBP - 0x100)), SourceClass::Stack));
+        assert!(matches!(names.classify_addr(&meta, &load_map, &format!("0x{:x}", RBP + 0x100)), SourceClass::Stack));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x1ffefffd28"), SourceClass::Unknown));
+
+        // in a data section of the binary
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x4010"), SourceClass::Global));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x5000"), SourceClass::Unknown));
+
+        let mut pie = LoadMap::new();
+        pie.add_valgrind_msg(&format!("--1234-- Reading syms from {}", BIN));
+        pie.add_valgrind_msg("--1234--    svma 0x0000001000, avma 0x0000109000");
+        assert!(matches!(names.classify_addr(&meta, &pie, "0x10c010"), SourceClass::Global));
+
+        // neither, e.g. on the heap, or no address at all
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x4a2c040"), SourceClass::Unknown));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x0"), SourceClass::Unknown));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "xyz"), SourceClass::Unknown));
+
+        // without the stack registers the usual stack ranges are used
+        names.regs.clear();
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0xffefffd28"), SourceClass::Stack));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x1ffefffd28"), SourceClass::Stack));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x7ffc1234abc0"), SourceClass::Stack));
+        assert!(matches!(names.classify_addr(&meta, &load_map, "0x4a2c040"), SourceClass::Unknown));
+    }
+
+    #[test]
+    fn global_ranges() {
+        let mut names = var_names();
+        let load_map = LoadMap::new();
+        assert_eq!(names.addr_name(BIN, 0x2000, &load_map, "0x4000"), Some("first".to_string()));
+        assert_eq!(names.addr_name(BIN, 0x2000, &load_map, "0x4027"), Some("g".to_string()));
+        assert_eq!(names.addr_name(BIN, 0x2000, &load_map, "0x4028"), None);
+        assert_eq!(names.addr_name(BIN, 0x2000, &load_map, "0x4010"), None);
+        // the static local is only found in its function
+        assert_eq!(names.addr_name(BIN, 0x1000, &load_map, "0x4010"), Some("count".to_string()));
+
+        // PIE addresses are translated to the object file
+        let mut pie = LoadMap::new();
+        pie.add_valgrind_msg(&format!("--1234-- Reading syms from {}", BIN));
+        pie.add_valgrind_msg("--1234--    svma 0x0000001000, avma 0x0000109000");
+        assert_eq!(names.addr_name(BIN, 0x2000, &pie, "0x10c020"), Some("g".to_string()));
+    }
+}
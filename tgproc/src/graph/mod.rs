@@ -3,6 +3,20 @@ extern crate regex;
 mod tgnode;
 pub mod meta;
 mod printer;
+mod merge;
+mod baseline;
+pub mod summary;
+mod json;
+mod sarif;
+mod dot;
+mod html;
+mod gcc;
+mod junit;
+mod annotate;
+mod export;
+mod libs;
+mod render;
+pub mod term;
 
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -13,29 +27,53 @@ use std::io::BufReader;
 use std::io::BufRead;
 use std::fs::File;
 use std::io::Result;
+use std::io::Error;
+use std::io::ErrorKind;
 use std::rc::Rc;
 use self::regex::Regex;
 
 pub use self::tgnode::TgNode;
+pub use self::tgnode::SinkKind;
 use self::tgnode::TgEdge;
 use self::tgnode::TgNodeMap;
+use self::tgnode::SourceClass;
 use self::meta::TgMetaDb;
 use self::meta::TgMetaNode;
+use self::meta::DebugInfoDb;
+use self::meta::LoadMap;
+use self::meta::VarNames;
+use self::meta::CallStack;
 use super::cli::Options;
 
 pub use self::printer::GraphPrinter;
+pub use self::merge::FindingMerger;
+pub use self::baseline::Baseline;
+pub use self::summary::Summary;
+pub use self::json::JsonPrinter;
+pub use self::sarif::SarifPrinter;
+pub use self::dot::DotPrinter;
+pub use self::html::HtmlReport;
+pub use self::gcc::GccPrinter;
+pub use self::junit::JunitPrinter;
+pub use self::annotate::Annotator;
+pub use self::render::Renderer;
 
 const PRINT_DETECTION_VERBOSITY: u8 = 20;
 
 pub struct Graph {
     pub sinks : Vec<Rc<TgNode>>,
     pub options : Options,
+    pub lines_parsed : usize,
+    pub nodes_kept : usize,
+    pub line_stats : Option<annotate::LineStatsMap>, // only collected for the annotate command
     idxwidth : usize,
+    source_filter : Vec<SourceClass>, // the classes of the sources of the traces
 }
 
 struct LineParts<'a> {
     loc : &'a str,
     cmd : &'a str,
+    value : &'a str,
     tnt_flow : &'a str
 }
 
@@ -45,12 +83,14 @@ impl<'a> LineParts<'a> {
 
         let loc_part = l_split.next();
         let cmd_part = l_split.next();
-        let tnt_flow = l_split.nth(2);
+        let value = l_split.next();
+        let tnt_flow = l_split.nth(1);
 
         tnt_flow.map(|flow| {
             LineParts {
                 loc: loc_part.unwrap(),
                 cmd: cmd_part.unwrap(),
+                value: value.unwrap(),
                 tnt_flow: flow
             }
         })
@@ -65,16 +105,53 @@ impl Graph {
         
         let mut graph = Graph {
             sinks : vec![],
-            options: options,
-            idxwidth: 8
+            options,
+            lines_parsed: 0,
+            nodes_kept: 0,
+            line_stats: None,
+            idxwidth: 8,
+            source_filter: vec![]
         };
+
+        graph.source_filter = match graph.options.sources.as_str() {
+            "all" => SourceClass::all(),
+            sources => sources.split(',')
+                .map(|c| SourceClass::from_name(c.trim())
+                     .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Unknown source class: {}", c))))
+                .collect::<Result<Vec<SourceClass>>>()?
+        };
+
+        if graph.options.command == "annotate" {
+            graph.line_stats = Some(HashMap::new());
+        }
+
+        let mut load_map = if graph.options.load_map.is_empty() {
+            LoadMap::new()
+        } else {
+            LoadMap::load(&graph.options.load_map)
+                .map_err(|e| Error::new(e.kind(), format!("{}: {}", graph.options.load_map, e)))?
+        };
+
+        let mut var_names = VarNames::new();
+        let mut call_stack = CallStack::new();
+        // the variables defined by the malloc wrapper, the sources reached over them are on the heap
+        let mut heap_vars = HashSet::new();
+        let libs = libs::LibClassifier::new(&graph.options.lib_globs, &graph.options.own_globs)?;
+        // the src: globs are matched against the source files from the debug info
+        let mut debug_db: Option<DebugInfoDb> = None; // created on the first src: glob match
+
+        let mut exporter = None;
+        if ! graph.options.export_graph.is_empty() {
+            exporter = Some(export::GraphExporter::new(&graph.options.export_graph, &graph.options.logfile)?);
+        }
         
-        let f = try!(File::open(&graph.options.logfile));
+        let f = File::open(&graph.options.logfile)?;
         let file = BufReader::new(&f);
         for (idx, line) in file.lines().enumerate() {
             graph.idxwidth = (idx+1).to_string().len();
+            graph.lines_parsed = idx+1;
             
-            let l : String = line.unwrap();
+            let l : String = line?;
 
             if let Some(lparts) = LineParts::new(&l) {
                 let (var, mut tgo) = TgNode::new(lparts.loc,
@@ -82,7 +159,25 @@ impl Graph {
                                                  lparts.tnt_flow,
                                                  idx,
                                                  &tg_ops);
-                let meta_node = TgMetaNode::new(l.clone(), lparts.loc);
+                let mut meta_node = TgMetaNode::new(l.clone(), lparts.loc)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("line {}: {}", idx+1, e)))?;
+                meta_node.var = var.clone();
+                if graph.options.simple_names {
+                    let func = meta::symbol::simplify(&meta_node.loc.func);
+                    meta_node.loc.full_func = Some(std::mem::replace(&mut meta_node.loc.func, func));
+                }
+                // without a source location the location names the object,
+                // otherwise only the load map knows it
+                let obj_addr = if meta_node.loc.lineno.is_none() {
+                    load_map.translate(&meta_node.loc.file, meta_node.loc.addr)
+                } else {
+                    load_map.object_at(meta_node.loc.addr).and_then(|obj| load_map.translate(obj, meta_node.loc.addr))
+                };
+                if let Some(obj_addr) = obj_addr {
+                    meta_node.loc.obj_addr = obj_addr;
+                }
+                meta_node.stack = Some(call_stack.step(&meta_node.loc));
+                meta_node.var_name = var_names.name_of(&meta_node, &load_map, lparts.cmd, lparts.value, lparts.tnt_flow);
                 
                 let mut kept = false;
                 let mut keep_reason = "";
@@ -97,20 +192,53 @@ impl Graph {
                                 .iter()
                                 .filter_map(|edge| edge.dest.clone())
                                 .collect::<Vec<Rc<TgNode>>>();
-                            Rc::get_mut(&mut tgo).unwrap().sink_reasons.extend_from_slice(reasons.as_slice())
+                            let node = Rc::get_mut(&mut tgo).unwrap();
+                            for reason in reasons {
+                                node.add_sink_reason(SinkKind::Manual, reason);
+                            }
                         }
                     } else {
-                        let sr: &mut Vec<Rc<TgNode>> = &mut Rc::get_mut(&mut tgo).unwrap().sink_reasons;
-                        
-                        sr.clear()
+                        Rc::get_mut(&mut tgo).unwrap().clear_sink_reasons();
                     }
                 }
+
+                if meta_node.loc.func == "__wrap_malloc" {
+                    heap_vars.extend(var.clone());
+                }
+                if tgo.is_source() {
+                    let class = if tgo.preds.iter().any(|edge| heap_vars.contains(&edge.via)) {
+                        SourceClass::Heap
+                    } else {
+                        var_names.classify_addr(&meta_node, &load_map, lparts.value)
+                    };
+                    Rc::get_mut(&mut tgo).unwrap().source_class = Some(class);
+                }
+
+                if let Some(ref mut stats) = graph.line_stats {
+                    annotate::add_line_stats(stats, &meta_node.loc, &tgo);
+                }
+
+                // the classification is only needed to drop library code or
+                // to show it, the source location only to match src: globs
+                let (is_lib, lib_reason) = if !graph.options.no_libs && graph.options.verbosity < 20 {
+                    (false, String::new())
+                } else if libs.needs_sources() {
+                    let mut src = meta_node.loc.clone();
+                    src.complete_info(debug_db.get_or_insert_with(DebugInfoDb::new));
+                    libs.classify(&meta_node, &src)
+                } else {
+                    libs.classify(&meta_node, &meta_node.loc)
+                };
+                if graph.options.verbosity >= 20 {
+                    eprintln!("{}    {}", if is_lib { "LIB " } else { "OWN " }, lib_reason);
+                }
                 
                 if let Some(ref v) = var {
                     if let Some(op) = tg_ops.get(v.as_str()) {
-                        panic!(format!("ERROR: Duplicated definition in lines {} and {}",
-                                       op.idx + 1,
-                                       idx+1))
+                        return Err(Error::new(ErrorKind::InvalidData,
+                                              format!("Duplicated definition in lines {} and {}",
+                                                      op.idx + 1,
+                                                      idx+1)));
                     }
 
                     let mut node_for_var = Some(tgo.clone());
@@ -120,17 +248,18 @@ impl Graph {
                     lazy_static! {
                         static ref RE_TMP_VAR: Regex = Regex::new(r"^t\d+_\d+$").unwrap();
                     }
-                    
+
+
                     // filter out unnecessary nodes
                     if ((meta_node.loc.func == "__wrap_write") || // __wrap_write is part of the instrumentation
                         (meta_node.loc.func == "__wrap_malloc") || // __wrap_malloc is part of the instrumentation
                         (graph.options.no_tmp_instr && RE_TMP_VAR.is_match(v)) ||
-                        (graph.options.no_libs && meta_node.is_lib()) ||
+                        (graph.options.no_libs && is_lib) ||
                         (graph.options.unique_locs && !locations.insert(meta_node.loc.addr))) {
                         if tgo.preds.is_empty() {
                             if graph.options.verbosity >= 20 {
-                                println!("REPLACING   {}", l);
-                                println!("BY          NONE");
+                                eprintln!("REPLACING   {}", l);
+                                eprintln!("BY          NONE");
                             }
                             node_for_var = None;
                             kept = false;
@@ -138,8 +267,8 @@ impl Graph {
                             if let TgEdge { dest: Some(ref pred), .. } = tgo.preds[0] {
                                 if pred.taint == tgo.taint { // no taint change occurred
                                     if graph.options.verbosity >= 20 {
-                                        println!("REPLACING   {}", l);
-                                        println!("BY          {}", meta_db.as_mut().unwrap().get_mut(pred).unwrap().line);
+                                        eprintln!("REPLACING   {}", l);
+                                        eprintln!("BY          {}", meta_db.as_mut().unwrap().get_mut(pred).unwrap().line);
                                     }
                                     // we just replace the node in the map with its pred
                                     node_for_var = Some(pred.clone());
@@ -147,8 +276,8 @@ impl Graph {
                                 }
                             } else {
                                 if graph.options.verbosity >= 20 {
-                                    println!("REPLACING   {}", l);
-                                    println!("BY          NONE");
+                                    eprintln!("REPLACING   {}", l);
+                                    eprintln!("BY          NONE");
                                 }
                                 node_for_var = None;
                                 kept = false;
@@ -177,21 +306,40 @@ impl Graph {
                 }
                 
                 if kept {
+                    graph.nodes_kept += 1;
+
                     if graph.options.verbosity >= 20 {
-                        println!("KEEP {}   {}", keep_reason, l);
+                        eprintln!("KEEP {}   {}", keep_reason, l);
                     }
                     
+                    if let Some(ref mut exp) = exporter {
+                        exp.add_node(&tgo, &meta_node)?;
+                    }
+
                     if let Some(ref mut mdb) = meta_db.as_mut() {
-                        mdb.insert(idx, meta_node);
+                        mdb.insert_node(&tgo, meta_node);
                     }
                 }
 
                 if graph.options.verbosity >= 20 {
-                    println!("");
+                    eprintln!();
                 }
+            } else {
+                load_map.add_valgrind_msg(&l);
+                var_names.add_valgrind_msg(&l);
             }
         }
 
+        if let Some(exp) = exporter {
+            exp.finish()?;
+        }
+
+        // the sinks whose sources are all filtered out are not reported at all
+        if graph.source_filter.len() < SourceClass::all().len() {
+            let sinks = std::mem::take(&mut graph.sinks);
+            graph.sinks = sinks.into_iter().filter(|sink| !graph.get_traces(sink).is_empty()).collect();
+        }
+
         Ok(graph)
     }
 
@@ -212,19 +360,23 @@ impl Graph {
 
         while let Some(op) = queue.pop_front() {
             if print_detection {
-                print!("{:>30}  detecting {:2$}",
-                       format!("{:?}", queue.iter().map(|n| n.idx+1).collect::<Vec<usize>>()),
-                       op.idx+1,
-                       self.idxwidth);
+                eprint!("{:>30}  detecting {:2$}",
+                        format!("{:?}", queue.iter().map(|n| n.idx+1).collect::<Vec<usize>>()),
+                        op.idx+1,
+                        self.idxwidth);
                 match *detected.get(op).unwrap() {
-                    Some(ref successor) => print!(" from {:1$}  --  ", successor.idx+1, self.idxwidth),
-                    None => print!("{:1$}  --  ", " ", self.idxwidth + 6)
+                    Some(successor) => eprint!(" from {:1$}  --  ", successor.idx+1, self.idxwidth),
+                    None => eprint!("{:1$}  --  ", " ", self.idxwidth + 6)
                 }
             }
 
             if op.is_source() {
-                if print_detection { println!("found source") }
-                sources.push(op);
+                if op.source_class.is_none_or(|c| self.source_filter.contains(&c)) {
+                    if print_detection { eprintln!("found source") }
+                    sources.push(op);
+                } else if print_detection {
+                    eprintln!("skipping source ({})", op.source_class.unwrap().name());
+                }
             } else {
                 let all_preds : Vec<&TgNode> = if op.is_sink() {
                     op.sink_reasons.iter().map(|pred| pred.as_ref()).collect()
@@ -247,9 +399,9 @@ impl Graph {
                 });
                 
                 if print_detection {
-                    println!("adding preds {:?} (skipping {:?})",
-                             preds.iter().map(|p| p.idx + 1).collect::<Vec<usize>>(),
-                             skipped.iter().map(|p| p.idx + 1).collect::<Vec<usize>>());
+                    eprintln!("adding preds {:?} (skipping {:?})",
+                              preds.iter().map(|p| p.idx + 1).collect::<Vec<usize>>(),
+                              skipped.iter().map(|p| p.idx + 1).collect::<Vec<usize>>());
                 }
 
                 for pred in preds.iter() {
@@ -276,5 +428,41 @@ impl Graph {
         paths
     }
 
+    /// Get the subgraph of all non-green nodes that reach the sink.
+    ///
+    /// Returns the nodes and the edges as (pred, succ, via) where via is the
+    /// variable over which succ was reached or the sink kind for sink reasons
+    pub fn get_subgraph<'l>(&self, sink: &'l TgNode) -> (Vec<&'l TgNode>, Vec<(&'l TgNode, &'l TgNode, &'l str)>) {
+        // again no recursion because of the size of the graph
+        let mut nodes = vec![sink];
+        let mut edges = vec![];
 
+        let mut queue: VecDeque<&TgNode> = VecDeque::new();
+        queue.push_back(sink);
+
+        let mut detected: HashSet<&TgNode> = HashSet::new();
+        detected.insert(sink);
+
+        while let Some(op) = queue.pop_front() {
+            let preds: Vec<(&TgNode, &str)> = if op.is_sink() {
+                let kind = op.sink_kind.unwrap().name();
+                op.sink_reasons.iter().map(|pred| (pred.as_ref(), kind)).collect()
+            } else {
+                op.preds.iter()
+                    .filter_map(|edge| edge.dest.as_ref().map(|p| (p.as_ref(), edge.via.as_str())))
+                    .collect()
+            };
+
+            for (pred, via) in preds.into_iter().filter(|&(p, _)| !p.is_green()) {
+                edges.push((pred, op, via));
+
+                if detected.insert(pred) {
+                    nodes.push(pred);
+                    queue.push_back(pred);
+                }
+            }
+        }
+
+        (nodes, edges)
+    }
 }
@@ -2,31 +2,83 @@ use super::Graph;
 use super::meta::TgMetaDb;
 use super::TgNode;
 use super::meta::TgMetaNode;
-use super::meta::DebugInfoDb;
+use super::meta::sources;
+use super::meta::callstack;
+use super::baseline::Baseline;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+use super::render::Suppression;
+use super::term::Columns;
 use ansi_term::Colour;
 use std::io::BufReader;
 use std::io::BufRead;
 use std::fs::File;
 use std::collections::HashSet;
+use std::io;
+use std::io::Write;
 
 pub struct GraphPrinter<'a, T: 'a + TgMetaDb> {
     graph: &'a Graph,
     meta_db: &'a mut T,
-    debug_db: DebugInfoDb,
+    findings: Findings,
     printed_srcs: HashSet<u64>,
     printed_sinks: HashSet<u64>,
-    skipped_traces: u32
+    ignored: Vec<(TgMetaNode, String, u32)>, // the suppressed sinks with their reason and count
+    skipped_traces: u32,
+    known_traces: u32,
+    color: bool,
+    width: Option<usize>
 }
 
 impl<'a, T: TgMetaDb> GraphPrinter<'a, T> {
     pub fn new<'b, U: TgMetaDb>(graph: &'b Graph, meta_db: &'b mut U) -> GraphPrinter<'b, U> {
         GraphPrinter {
-            graph: graph,
-            meta_db: meta_db,
-            debug_db: DebugInfoDb::new(),
+            graph,
+            meta_db,
+            findings: Findings::new(),
             printed_srcs: HashSet::new(),
             printed_sinks: HashSet::new(),
-            skipped_traces: 0
+            ignored: vec![],
+            skipped_traces: 0,
+            known_traces: 0,
+            color: graph.options.color,
+            width: None
+        }
+    }
+
+    /// Overrides the --no-color option, e.g. when writing to a file
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// Fit the columns to the width of the terminal; without a width they have
+    /// fixed widths
+    pub fn set_width(&mut self, width: Option<usize>) {
+        self.width = width;
+    }
+
+    /// Write the fingerprints of all findings to a baseline file
+    pub fn write_baseline(&mut self, path: &str) -> io::Result<()> {
+        let mut baseline = Baseline::new();
+
+        for sink in self.graph.sinks.iter() {
+            for trace in self.findings.traces_of(self.graph, self.meta_db, sink) {
+                baseline.insert(Findings::fingerprint(self.meta_db, sink, trace.nodes[0]));
+            }
+        }
+
+        baseline.write(path)
+    }
+
+    /// Remember a sink with an asb-ignore comment for the list at the end
+    fn add_ignored(&mut self, sink: &TgNode, reason: &str) {
+        let meta = self.meta_db.get(sink).unwrap();
+
+        if let Some(entry) = self.ignored.iter_mut().find(|e| e.0.loc == meta.loc) {
+            entry.2 += 1;
+        } else {
+            self.ignored.push((meta.clone(), reason.to_string(), 1));
         }
     }
 
@@ -34,20 +86,30 @@ impl<'a, T: TgMetaDb> GraphPrinter<'a, T> {
      * @return true if this one was completely skipped
      */
     pub fn print_traces_of(&mut self,
-                           sink: &TgNode) -> bool {
+                           out: &mut dyn Write,
+                           sink: &TgNode) -> io::Result<bool> {
         let mut completely_skipped = true;
         if self.graph.options.single_sink {
             let sink_addr = self.meta_db.get(sink).unwrap().loc.addr;
             if !self.printed_sinks.insert(sink_addr) {
                 // if printed_sinks already contains this address we skip this one
                 self.skipped_traces += 1;
-                return completely_skipped;
+                return Ok(completely_skipped);
             }
         }
 
-        for (tidx,trace) in self.graph.get_traces(sink).iter().enumerate() {
+        let kind = sink.sink_kind.unwrap();
+        let traces = self.findings.traces_of(self.graph, self.meta_db, sink);
+        if let Some(Suppression::InSource(reason)) = traces.first().and_then(|t| t.suppression.as_ref()) {
+            self.add_ignored(sink, reason);
+            self.findings.count_all(kind, &traces);
+            return Ok(completely_skipped);
+        }
+
+        let mut tidx = 0;
+        for t in traces {
             if self.graph.options.single_src {
-                let src = trace[0];
+                let src = t.nodes[0];
                 let src_addr = self.meta_db.get(src).unwrap().loc.addr;
                 if !self.printed_sinks.insert(src_addr) {
                     // if printed_srcs already contains this address we skip this one
@@ -55,21 +117,38 @@ impl<'a, T: TgMetaDb> GraphPrinter<'a, T> {
                     continue;
                 }
             }
+
+            // only the traces that are not dropped above are counted as
+            // printed or skipped by the findings
+            self.findings.count(kind, &t);
+            if t.suppression.is_some() {
+                // this one is already known from the baseline
+                self.known_traces += 1;
+                continue;
+            }
+            let trace = t.nodes;
             
             // separate each source
             if tidx > 0 {
-                self.print_sep("--------------------------------------------------------------------------------", Colour::Yellow);
+                self.print_sep(out, "--------------------------------------------------------------------------------", Colour::Yellow)?;
             }
+            tidx += 1;
 
-            println!(">>>> The origin of the taint should be just here <<<<");
+            match trace[0].source_class {
+                Some(class) => writeln!(out, ">>>> The origin of the taint should be just here ({}) <<<<", class.name())?,
+                None => writeln!(out, ">>>> The origin of the taint should be just here <<<<")?
+            }
             completely_skipped = false;
 
             if self.graph.options.src_only {
                 // print only the source, not the whole trace
                 let src = trace[0];
-                let meta = self.meta_db.get_mut(src).unwrap();
-                meta.loc.complete_info(&mut self.debug_db);
-                src.print(meta, self.graph.options.color);
+                let meta = self.meta_db.get(src).unwrap();
+                let cols = Columns::new(&[meta], self.width);
+                let context = self.graph.options.context;
+                GraphPrinter::<T>::print_context(out, &cols, meta, true, context)?;
+                src.write(out, &cols.format(meta), self.color)?;
+                GraphPrinter::<T>::print_context(out, &cols, meta, false, context)?;
             } else if self.graph.options.mark_trace {
                 // print the whole taintgrind trace
                 let f = File::open(&self.graph.options.logfile).unwrap();
@@ -79,59 +158,64 @@ impl<'a, T: TgMetaDb> GraphPrinter<'a, T> {
                 
                 for (idx, l) in file.lines().enumerate() {
                     let line = l.unwrap();
-                    print!("{:8}   ", idx+1);
+                    write!(out, "{:8}   ", idx+1)?;
 
-                    if trace_iter.peek().map_or(false, |n| n.idx == idx) {
+                    if trace_iter.peek().is_some_and(|n| n.idx == idx) {
                         let node = trace_iter.next().unwrap();
 
-                        if self.graph.options.color {
+                        if self.color {
                             let clr = node.taint.color();
                             if node.is_sink() {
-                                println!("{}", clr.bold().paint(line))
+                                writeln!(out, "{}", clr.bold().paint(line))?
                             } else {
-                                println!("{}", clr.paint(line))
+                                writeln!(out, "{}", clr.paint(line))?
                             }
                         } else {
                             if node.is_sink() {
-                                print!("*{}*  ", node.taint.abbrv())
+                                write!(out, "*{}*  ", node.taint.abbrv())?
                             } else {
-                                print!("[{}]  ", node.taint.abbrv())
+                                write!(out, "[{}]  ", node.taint.abbrv())?
                             }
-                            println!("{}", line);
+                            writeln!(out, "{}", line)?;
                         }
                         
                     } else {
-                        if ! self.graph.options.color {
-                            print!("     ");
+                        if ! self.color {
+                            write!(out, "     ")?;
                         }
-                        println!("{}", line);
+                        writeln!(out, "{}", line)?;
                     }
                 }
             } else if self.graph.options.taintgrind_trace {
                 // print the taintgrind lines of the trace instead of the source lines
-                for node in trace {
-                    let meta: &mut TgMetaNode = self.meta_db.get_mut(node).unwrap();
-                    println!("{}", node.taint.paint(&meta.line))
+                for node in trace.iter() {
+                    let meta: &TgMetaNode = self.meta_db.get(node).unwrap();
+                    if self.color {
+                        writeln!(out, "{}", node.taint.paint(&meta.named_line()))?
+                    } else {
+                        writeln!(out, "{}", meta.named_line())?
+                    }
                 }
             } else {
                 // default behavior: print the source lines of the trace
-                for node in trace {
-                    let meta: &mut TgMetaNode = self.meta_db.get_mut(node).unwrap();
-                    meta.loc.complete_info(&mut self.debug_db);
-                }
+                let metas: Vec<&TgMetaNode> = trace.iter().map(|node| self.meta_db.get(node).unwrap()).collect();
+                let cols = Columns::new(&metas, self.width);
 
+                // collect the lines to print first, the context is shown
+                // around the first (the source) and the last one (the sink)
+                let mut rows: Vec<(&TgNode, &TgMetaNode)> = vec![];
                 let mut prev_meta : Option<&TgMetaNode> = None;
                 let mut prev_node : Option<&TgNode> = None;
                 
-                for node in trace {
+                for node in trace.iter() {
                     let meta: &TgMetaNode = self.meta_db.get(node).unwrap();
 
-                    if let Some(ref pn) = prev_node {
+                    if let Some(pn) = prev_node {
                         let pm = prev_meta.unwrap();
                         // don't print the same line twice, however, we have to print the last
                         // occurrence in order to get the taint right
                         if meta.loc != pm.loc {
-                            pn.print(pm, self.graph.options.color);
+                            rows.push((pn, pm));
                         }
                     }
 
@@ -139,48 +223,159 @@ impl<'a, T: TgMetaDb> GraphPrinter<'a, T> {
                     prev_meta = Some(meta)
                 }
 
-                if let Some(ref pn) = prev_node {
-                    pn.print(prev_meta.unwrap(), self.graph.options.color);
+                if let Some(pn) = prev_node {
+                    rows.push((pn, prev_meta.unwrap()));
+                }
+
+                // show the call path where it changes if the trace leaves a function
+                let show_paths = self.graph.options.backtrace &&
+                    rows.iter().any(|&(_, meta)| meta.stack.as_ref().is_some_and(|s| s.parent.is_some()));
+                let mut prev_path = None;
+
+                let context = self.graph.options.context;
+                for (ridx, &(node, meta)) in rows.iter().enumerate() {
+                    if show_paths {
+                        let path = meta.stack.as_ref().map(|s| (s.path().join(" > "), s.is_reconstructed()));
+                        if let Some((ref p, reconstructed)) = path {
+                            if path != prev_path {
+                                writeln!(out, "  in {}{}", p, if reconstructed { " (reconstructed)" } else { "" })?;
+                            }
+                        }
+                        prev_path = path;
+                    }
+                    let with_context = context > 0 && (ridx == 0 || ridx == rows.len() - 1);
+                    if with_context {
+                        GraphPrinter::<T>::print_context(out, &cols, meta, true, context)?;
+                    }
+                    node.write(out, &cols.format(meta), self.color)?;
+                    if with_context {
+                        GraphPrinter::<T>::print_context(out, &cols, meta, false, context)?;
+                    }
                 }
             }
         }
 
-        completely_skipped
+        if !completely_skipped && self.graph.options.backtrace && !self.graph.options.src_only && !self.graph.options.mark_trace {
+            self.print_backtrace(out, sink)?;
+        }
+
+        Ok(completely_skipped)
+    }
+
+    /// Print the call stack of the sink like a debugger does, unless the sink
+    /// is in the outermost function
+    fn print_backtrace(&mut self, out: &mut dyn Write, sink: &TgNode) -> io::Result<()> {
+        let meta = self.meta_db.get(sink).unwrap();
+        let frames = callstack::backtrace(meta, &mut self.findings.debug_db);
+        if frames.len() < 2 {
+            return Ok(());
+        }
+
+        writeln!(out, "Backtrace of the sink:")?;
+        for (idx, frame) in frames.iter().enumerate() {
+            writeln!(out, "  #{:<2} {} at {}:{}{}{}",
+                     idx,
+                     frame.func,
+                     frame.file,
+                     frame.lineno.map_or("?".to_string(), |l| l.to_string()),
+                     if frame.inlined { " (inlined)" } else { "" },
+                     if frame.reconstructed { " (reconstructed)" } else { "" })?;
+        }
+        Ok(())
+    }
+
+    /// Print up to n lines of source code before or after the line of the node
+    fn print_context(out: &mut dyn Write,
+                     cols: &Columns,
+                     meta: &TgMetaNode,
+                     before: bool,
+                     n: usize) -> io::Result<()> {
+        let (lineno, lines) = match (meta.loc.lineno, sources::lines(meta.loc.path())) {
+            // the debug info may not match the file, e.g. if it was changed
+            (Some(lineno), Some(lines)) if lineno > 0 && lineno <= lines.len() => (lineno, lines),
+            _ => return Ok(())
+        };
+
+        let range = if before {
+            lineno.saturating_sub(n).max(1)..lineno
+        } else {
+            lineno+1..lineno.saturating_add(n).min(lines.len()) + 1
+        };
+
+        for ctx_lineno in range {
+            writeln!(out, "  {}", cols.format_context(ctx_lineno, lines[ctx_lineno-1].trim()))?;
+        }
+        Ok(())
     }
 
-    pub fn print_traces(&mut self) {
+    pub fn print_traces(&mut self, out: &mut dyn Write) -> io::Result<()> {
         self.printed_srcs.clear();
         self.printed_sinks.clear();
         self.skipped_traces = 0;
+        self.known_traces = 0;
+        self.ignored.clear();
+        self.findings.reset(self.graph);
 
         let mut skipped_last = false;
         
         for (sidx,sink) in self.graph.sinks.iter().enumerate() {
             // separate each sink
             if sidx > 0 && !skipped_last {
-                self.print_sink_sep();
+                self.print_sink_sep(out)?;
             }
 
-            skipped_last = self.print_traces_of(sink);
+            skipped_last = self.print_traces_of(out, sink)?;
         }
 
-        if self.graph.options.single_src || self.graph.options.single_sink {
-            if !skipped_last {
-                self.print_sink_sep();
+        let print_skipped = self.graph.options.single_src || self.graph.options.single_sink;
+        if (print_skipped || self.findings.has_baseline() || !self.ignored.is_empty()) && !skipped_last {
+            self.print_sink_sep(out)?;
+        }
+        if !self.ignored.is_empty() {
+            writeln!(out, "Ignored sinks:")?;
+            let metas: Vec<&TgMetaNode> = self.ignored.iter().map(|e| &e.0).collect();
+            let mut cols = Columns::new(&metas, self.width);
+            cols.set_indent("    1x ".len());
+            for &(ref meta, ref reason, count) in self.ignored.iter() {
+                writeln!(out, "{:5}x {}", count, cols.format(meta))?;
+                writeln!(out, "        asb-ignore: {}", reason)?;
             }
-            println!("{} traces skipped.", self.skipped_traces);
         }
+        if print_skipped {
+            writeln!(out, "{} traces skipped.", self.skipped_traces)?;
+        }
+        if self.findings.has_baseline() {
+            writeln!(out, "{} known traces hidden by the baseline.", self.known_traces)?;
+        }
+
+        // the known traces are already counted by the findings
+        self.findings.summary.traces_skipped += self.skipped_traces as usize;
+        Ok(())
     }
 
-    fn print_sink_sep(&self) {
-        self.print_sep("================================================================================", Colour::Green);
+    fn print_sink_sep(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.print_sep(out, "================================================================================", Colour::Green)
     }
 
-    fn print_sep(&self, sep: &str, color: Colour) {
-        if self.graph.options.color {
-            println!("{}", color.paint(sep));
+    fn print_sep(&self, out: &mut dyn Write, sep: &str, color: Colour) -> io::Result<()> {
+        if self.color {
+            writeln!(out, "{}", color.paint(sep))
         } else {
-            println!("{}", sep);
+            writeln!(out, "{}", sep)
         }
     }
 }
+
+impl<'a, T: TgMetaDb> Renderer for GraphPrinter<'a, T> {
+    fn render(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.print_traces(out)
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::Result;
+use std::io::Write;
+
+use super::Graph;
+use super::TgNode;
+use super::SinkKind;
+use super::baseline::Baseline;
+use super::meta::TgMetaDb;
+use super::meta::DebugInfoDb;
+use super::summary::Summary;
+
+/// The common interface of the output formats that are rendered from one graph
+/// and its meta DB. The renderers write to the given output instead of stdout,
+/// so that several of them can be rendered from a single parse of the log.
+pub trait Renderer {
+    /// Render all sinks of the graph to `out`
+    fn render(&mut self, out: &mut dyn Write) -> Result<()>;
+
+    /// The step shared by all renderers that decides which traces are reported
+    fn findings(&mut self) -> &mut Findings;
+
+    /// The statistics of the last call to `render`
+    fn summary(&self) -> &Summary;
+}
+
+/// Why a trace is not reported
+#[derive(Clone)]
+pub enum Suppression {
+    InSource(String), // the reason of the asb-ignore comment at the sink
+    Baseline // the finding is known from the baseline
+}
+
+/// A trace from a source (the first node) to a sink (the last node)
+pub struct Trace<'l> {
+    pub nodes: Vec<&'l TgNode>,
+    pub suppression: Option<Suppression>
+}
+
+/// Decides for every renderer which traces are findings, so that all formats
+/// report the same ones and the exit code does not depend on the format. A
+/// trace is suppressed by an asb-ignore comment at its sink or by the baseline;
+/// only the other ones are counted as printed in the summary. Renderers count
+/// the traces after their own filtering, see `count`.
+pub struct Findings {
+    pub debug_db: DebugInfoDb,
+    baseline: Option<Baseline>,
+    pub summary: Summary
+}
+
+impl Findings {
+    pub fn new() -> Findings {
+        Findings {
+            debug_db: DebugInfoDb::new(),
+            baseline: None,
+            summary: Summary::new()
+        }
+    }
+
+    /// Suppress all findings that are contained in the baseline
+    pub fn set_baseline(&mut self, baseline: Baseline) {
+        self.baseline = Some(baseline);
+    }
+
+    pub fn has_baseline(&self) -> bool {
+        self.baseline.is_some()
+    }
+
+    /// Start a new rendering of the graph
+    pub fn reset(&mut self, graph: &Graph) {
+        self.summary = Summary::new();
+        self.summary.add_graph(graph);
+    }
+
+    /// The traces of the sink with the locations of the sink and of all trace
+    /// nodes completed
+    pub fn traces_of<'l, T: TgMetaDb>(&mut self, graph: &Graph, meta_db: &mut T, sink: &'l TgNode) -> Vec<Trace<'l>> {
+        let traces = graph.get_traces(sink);
+
+        for node in traces.iter().flat_map(|t| t.iter()).chain(Some(&sink)) {
+            if let Some(meta) = meta_db.get_mut(node) {
+                meta.loc.complete_info(&mut self.debug_db);
+            }
+        }
+
+        let ignored = meta_db.get(sink).and_then(|m| m.loc.asb_ignore_reason());
+
+        traces.into_iter().map(|nodes| {
+            let suppression = if let Some(ref reason) = ignored {
+                Some(Suppression::InSource(reason.clone()))
+            } else if self.baseline.as_ref().is_some_and(|b| b.contains(&Findings::fingerprint(meta_db, sink, nodes[0]))) {
+                Some(Suppression::Baseline)
+            } else {
+                None
+            };
+
+            Trace { nodes, suppression }
+        }).collect()
+    }
+
+    /// Count a trace of the sink kind in the summary: as skipped if it is
+    /// suppressed, else as printed
+    pub fn count(&mut self, kind: SinkKind, trace: &Trace) {
+        if trace.suppression.is_some() {
+            self.summary.traces_skipped += 1;
+        } else {
+            self.summary.add_printed(kind);
+        }
+    }
+
+    /// Count all traces of the sink kind, for the renderers that report every trace
+    pub fn count_all(&mut self, kind: SinkKind, traces: &[Trace]) {
+        for trace in traces.iter() {
+            self.count(kind, trace);
+        }
+    }
+
+    /// The baseline fingerprint of the finding from the source to the sink;
+    /// the locations of both have to be completed
+    pub fn fingerprint<T: TgMetaDb>(meta_db: &T, sink: &TgNode, src: &TgNode) -> String {
+        Baseline::fingerprint(sink.sink_kind.unwrap(),
+                              &meta_db.get(sink).unwrap().loc,
+                              &meta_db.get(src).unwrap().loc)
+    }
+}
@@ -0,0 +1,245 @@
+use serde_json;
+use serde_json::Value;
+use std::env;
+use std::io::Result;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::Graph;
+use super::TgNode;
+use super::SinkKind;
+use super::meta::TgMetaDb;
+use super::meta::SrcLoc;
+use super::meta::callstack;
+use super::summary::Summary;
+use super::render::Renderer;
+use super::render::Findings;
+use super::render::Suppression;
+use super::render::Trace;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The base of the relative artifact URIs, i.e. the current directory
+const SRCROOT: &str = "%SRCROOT%";
+
+/// Prints all sinks as results of a SARIF 2.1.0 log. Each trace of a sink
+/// becomes a code flow, so SARIF viewers can show it step by step. The sinks
+/// with an asb-ignore comment get an inSource suppression. With a baseline the
+/// results have a baselineState and the known ones an external suppression.
+/// The files below the current directory are given relative to %SRCROOT%.
+pub struct SarifPrinter<'a, T: 'a + TgMetaDb> {
+    graph: &'a Graph,
+    meta_db: &'a mut T,
+    findings: Findings
+}
+
+impl<'a, T: TgMetaDb> SarifPrinter<'a, T> {
+    pub fn new(graph: &'a Graph, meta_db: &'a mut T) -> SarifPrinter<'a, T> {
+        SarifPrinter {
+            graph,
+            meta_db,
+            findings: Findings::new()
+        }
+    }
+
+    fn sink_to_result(&mut self, sink: &TgNode) -> Value {
+        let all_traces = self.findings.traces_of(self.graph, self.meta_db, sink);
+        let kind = sink.sink_kind.unwrap();
+        self.findings.count_all(kind, &all_traces);
+
+        // only the new traces are shown unless the whole sink is known
+        let known = |t: &Trace| matches!(t.suppression, Some(Suppression::Baseline));
+        let all_known = all_traces.iter().all(known);
+        let traces: Vec<&Trace> = all_traces.iter().filter(|t| all_known || !known(t)).collect();
+
+        let code_flows: Vec<Value> = traces.iter().map(|trace| json!({
+            "threadFlows": [{
+                "locations": trace.nodes.iter().map(|node| self.thread_flow_location(node)).collect::<Vec<Value>>()
+            }]
+        })).collect();
+
+        let mut result = json!({
+            "ruleId": kind.name(),
+            "level": "warning",
+            "message": { "text": format!("{} (taintgrind log line {})", kind.description(), sink.idx + 1) },
+            "locations": [{ "physicalLocation": self.physical_location(sink) }],
+            "codeFlows": code_flows
+        });
+
+        if let Some(meta) = self.meta_db.get(sink) {
+            let frames: Vec<Value> = callstack::backtrace(meta, &mut self.findings.debug_db).iter().map(|frame| {
+                let mut phys = json!({ "artifactLocation": SarifPrinter::<T>::artifact_location(Path::new(&frame.file)) });
+                if let Some(lineno) = frame.lineno {
+                    phys["region"] = json!({ "startLine": lineno });
+                }
+                let mut stack_frame = json!({
+                    "location": {
+                        "physicalLocation": phys,
+                        "logicalLocations": [{ "name": frame.func, "kind": "function" }]
+                    }
+                });
+                if frame.reconstructed {
+                    stack_frame["properties"] = json!({ "reconstructed": true });
+                }
+                stack_frame
+            }).collect();
+            result["stacks"] = json!([{ "message": { "text": "backtrace of the sink" }, "frames": frames }]);
+        }
+
+        if self.findings.has_baseline() {
+            result["baselineState"] = json!(if all_known && !all_traces.is_empty() { "unchanged" } else { "new" });
+        }
+
+        match all_traces.first().and_then(|t| t.suppression.clone()) {
+            Some(Suppression::InSource(reason)) => {
+                result["suppressions"] = json!([{ "kind": "inSource", "justification": reason }]);
+            },
+            Some(Suppression::Baseline) if all_known => {
+                result["suppressions"] = json!([{ "kind": "external", "justification": "known finding from the baseline" }]);
+            },
+            _ => ()
+        }
+
+        result
+    }
+
+    fn thread_flow_location(&self, node: &TgNode) -> Value {
+        let taint = match node.source_class {
+            Some(class) => format!("{} ({} source)", node.taint.name(), class.name()),
+            None => node.taint.name().to_string()
+        };
+        let text = match self.meta_db.get(node) {
+            Some(meta) => format!("{}: {}", taint, meta.loc.src_line.as_ref().unwrap_or(&meta.line)),
+            None => taint
+        };
+
+        json!({
+            "location": {
+                "physicalLocation": self.physical_location(node),
+                "message": { "text": text }
+            },
+            "importance": if node.is_source() || node.is_sink() { "essential" } else { "important" }
+        })
+    }
+
+    fn physical_location(&self, node: &TgNode) -> Value {
+        match self.meta_db.get(node) {
+            Some(meta) => SarifPrinter::<T>::loc_to_physical_location(&meta.loc),
+            None => json!({ "artifactLocation": SarifPrinter::<T>::artifact_location(Path::new(&self.graph.options.logfile)),
+                            "region": { "startLine": node.idx + 1 } })
+        }
+    }
+
+    fn loc_to_physical_location(loc: &SrcLoc) -> Value {
+        let mut phys = json!({
+            "artifactLocation": SarifPrinter::<T>::artifact_location(&loc.project_path()),
+            "address": { "absoluteAddress": loc.addr }
+        });
+
+        if let Some(lineno) = loc.lineno {
+            phys["region"] = json!({ "startLine": lineno });
+            if let Some(column) = loc.column {
+                phys["region"]["startColumn"] = json!(column);
+            }
+        }
+
+        phys
+    }
+
+    /// The artifactLocation of the file: a URI relative to %SRCROOT% if the
+    /// file is below the current directory, else an absolute file URI
+    fn artifact_location(path: &Path) -> Value {
+        let cwd = env::current_dir().unwrap_or_default();
+        let path: PathBuf = path.components().filter(|c| *c != Component::CurDir).collect();
+        let abspath = if path.is_absolute() { path.clone() } else { cwd.join(&path) };
+
+        match abspath.strip_prefix(&cwd) {
+            Ok(relpath) if !relpath.starts_with("..") => json!({ "uri": uri(relpath), "uriBaseId": SRCROOT }),
+            _ => json!({ "uri": format!("file://{}", uri(&abspath)) })
+        }
+    }
+}
+
+/// The path as URI reference, all characters except the unreserved ones and
+/// the slashes are percent-encoded
+fn uri(path: &Path) -> String {
+    let mut uri = String::new();
+    for &b in path.to_string_lossy().as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(b as char),
+            _ => uri.push_str(&format!("%{:02X}", b))
+        }
+    }
+    uri
+}
+
+impl<'a, T: TgMetaDb> Renderer for SarifPrinter<'a, T> {
+    fn render(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.findings.reset(self.graph);
+
+        let results: Vec<Value> = self.graph.sinks.iter().map(|sink| self.sink_to_result(sink)).collect();
+
+        let rules: Vec<Value> = SinkKind::all().iter().map(|kind| json!({
+            "id": kind.name(),
+            "shortDescription": { "text": kind.description() },
+            "defaultConfiguration": { "level": "warning" }
+        })).collect();
+
+        let doc = json!({
+            "$schema": SARIF_SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "originalUriBaseIds": {
+                    SRCROOT: { "uri": format!("file://{}/", uri(&env::current_dir().unwrap_or_default())) }
+                },
+                "tool": {
+                    "driver": {
+                        "name": "tgproc",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules
+                    }
+                },
+                "results": results
+            }]
+        });
+
+        writeln!(out, "{}", serde_json::to_string_pretty(&doc).unwrap())
+    }
+
+    fn findings(&mut self) -> &mut Findings {
+        &mut self.findings
+    }
+
+    fn summary(&self) -> &Summary {
+        &self.findings.summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::meta::SimpleMetaDB;
+
+    fn location(path: &str) -> Value {
+        SarifPrinter::<SimpleMetaDB>::artifact_location(Path::new(path))
+    }
+
+    #[test]
+    fn uris() {
+        assert_eq!(uri(Path::new("src/a b/c%d.c")), "src/a%20b/c%25d.c");
+        assert_eq!(uri(Path::new("/x/ä#1.c")), "/x/%C3%A4%231.c");
+    }
+
+    #[test]
+    fn artifact_locations() {
+        assert_eq!(location("./src/a.c"), json!({ "uri": "src/a.c", "uriBaseId": "%SRCROOT%" }));
+
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(location(cwd.join("src/a b.c").to_str().unwrap()),
+                   json!({ "uri": "src/a%20b.c", "uriBaseId": "%SRCROOT%" }));
+        assert_eq!(location("/nonexistent/a b.c"), json!({ "uri": "file:///nonexistent/a%20b.c" }));
+        assert_eq!(location("../a.c"), json!({ "uri": format!("file://{}", uri(&cwd.join("../a.c"))) }));
+    }
+}
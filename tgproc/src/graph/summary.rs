@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use super::Graph;
+use super::SinkKind;
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FINDINGS: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+/// Statistics about one or more analysed logs that are printed at the end of
+/// a run and decide about the exit code
+#[derive(Clone)]
+pub struct Summary {
+    pub lines_parsed: usize,
+    pub nodes_kept: usize,
+    pub sinks: BTreeMap<SinkKind, usize>,
+    pub traces_printed: BTreeMap<SinkKind, usize>,
+    pub traces_skipped: usize,
+    pub errors: usize
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary {
+            lines_parsed: 0,
+            nodes_kept: 0,
+            sinks: BTreeMap::new(),
+            traces_printed: BTreeMap::new(),
+            traces_skipped: 0,
+            errors: 0
+        }
+    }
+
+    pub fn add_graph(&mut self, graph: &Graph) {
+        self.lines_parsed += graph.lines_parsed;
+        self.nodes_kept += graph.nodes_kept;
+
+        for sink in graph.sinks.iter() {
+            *self.sinks.entry(sink.sink_kind.unwrap()).or_insert(0) += 1;
+        }
+    }
+
+    /// Add the statistics of another log
+    pub fn add(&mut self, other: &Summary) {
+        self.lines_parsed += other.lines_parsed;
+        self.nodes_kept += other.nodes_kept;
+        for (kind, count) in other.sinks.iter() {
+            *self.sinks.entry(*kind).or_insert(0) += count;
+        }
+        for (kind, count) in other.traces_printed.iter() {
+            *self.traces_printed.entry(*kind).or_insert(0) += count;
+        }
+        self.traces_skipped += other.traces_skipped;
+        self.errors += other.errors;
+    }
+
+    pub fn add_printed(&mut self, kind: SinkKind) {
+        *self.traces_printed.entry(kind).or_insert(0) += 1;
+    }
+
+    /// The exit code is EXIT_FINDINGS if a trace of one of the fail_on sink kinds was printed
+    pub fn exit_code(&self, fail_on: &[SinkKind]) -> i32 {
+        if self.errors > 0 {
+            EXIT_ERROR
+        } else if fail_on.iter().any(|k| self.traces_printed.contains_key(k)) {
+            EXIT_FINDINGS
+        } else {
+            EXIT_OK
+        }
+    }
+
+    pub fn print(&self) {
+        eprintln!("lines parsed:   {}", self.lines_parsed);
+        eprintln!("nodes kept:     {}", self.nodes_kept);
+        eprintln!("sinks:          {}", Summary::by_kind(&self.sinks));
+        eprintln!("traces printed: {}", Summary::by_kind(&self.traces_printed));
+        eprintln!("traces skipped: {}", self.traces_skipped);
+        if self.errors > 0 {
+            eprintln!("errors:         {}", self.errors);
+        }
+    }
+
+    fn by_kind(counts: &BTreeMap<SinkKind, usize>) -> String {
+        let total: usize = counts.values().sum();
+        if counts.is_empty() {
+            return total.to_string();
+        }
+
+        let kinds: Vec<String> = counts.iter().map(|(k, c)| format!("{} {}", c, k.name())).collect();
+        format!("{} ({})", total, kinds.join(", "))
+    }
+}
@@ -0,0 +1,265 @@
+use libc;
+
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::mem;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+
+use super::meta::TgMetaNode;
+
+const MIN_FILE_WIDTH: usize = 12;
+const MIN_FUNC_WIDTH: usize = 10;
+const MIN_LINE_WIDTH: usize = 20;
+/// The text before the columns, e.g. the taint of the node and a space
+const DEFAULT_INDENT: usize = 2;
+/// The fixed columns used if the width of the terminal is not known
+const FIXED_FILE_WIDTH: usize = 29;
+const FIXED_FUNC_WIDTH: usize = 20;
+
+pub fn stdout_is_tty() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// Colors are used if stdout is a terminal and NO_COLOR is not set (see no-color.org)
+pub fn use_color() -> bool {
+    stdout_is_tty() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// The width of the terminal on stdout or None if stdout is not a terminal
+pub fn width() -> Option<usize> {
+    if !stdout_is_tty() {
+        return None;
+    }
+
+    if let Some(cols) = env::var("COLUMNS").ok().and_then(|c| c.parse::<usize>().ok()) {
+        return Some(cols);
+    }
+
+    unsafe {
+        let mut ws: libc::winsize = mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) == 0 && ws.ws_col > 0 {
+            Some(ws.ws_col as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// Shorten a string to at most max chars by replacing its middle with "…",
+/// e.g. the start and the end of a path are usually more telling than the middle
+pub fn truncate_middle(s: &str, max: usize) -> String {
+    let len = s.chars().count();
+    if len <= max || max == 0 {
+        return s.to_string();
+    }
+
+    let tail = (max - 1) / 2;
+    let head = max - 1 - tail;
+    let mut short: String = s.chars().take(head).collect();
+    short.push('…');
+    short.extend(s.chars().skip(len - tail));
+    short
+}
+
+/// Shorten a string to at most max chars by replacing its end with "…"
+pub fn truncate_end(s: &str, max: usize) -> String {
+    if s.chars().count() <= max || max == 0 {
+        return s.to_string();
+    }
+
+    let mut short: String = s.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+/// The widths of the file, line number and function columns of the text output
+pub struct Columns {
+    file: usize,
+    lineno: usize,
+    func: usize,
+    truncate: bool, // shorten the file and function to the width of their column
+    width: Option<usize> // the width of the terminal
+}
+
+impl Columns {
+    /// If the width of the terminal is known the columns are as wide as needed
+    /// for the given nodes, but the file and function columns are limited, so
+    /// that there is still room for the source line, which is cut off at the
+    /// end of the terminal line. Otherwise, e.g. when the output goes to a
+    /// file, the columns have the fixed widths {:>29}:{:04}: {:>20}: that
+    /// longer files and functions overflow, and the source line is not cut off.
+    pub fn new(metas: &[&TgMetaNode], width: Option<usize>) -> Columns {
+        let w = match width {
+            Some(w) => w,
+            None => return Columns { file: FIXED_FILE_WIDTH, lineno: 4, func: FIXED_FUNC_WIDTH, truncate: false, width: None }
+        };
+
+        let mut cols = Columns { file: 0, lineno: 4, func: 0, truncate: true, width: Some(w.saturating_sub(DEFAULT_INDENT)) };
+
+        for meta in metas.iter() {
+            cols.file = cols.file.max(meta.loc.rel_path().display().to_string().chars().count());
+            cols.func = cols.func.max(meta.loc.func.chars().count());
+            if let Some(lineno) = meta.loc.lineno {
+                cols.lineno = cols.lineno.max(lineno.to_string().len());
+            }
+        }
+
+        cols.file = cols.file.min(MIN_FILE_WIDTH.max(w * 3 / 10));
+        cols.func = cols.func.min(MIN_FUNC_WIDTH.max(w / 5));
+
+        cols
+    }
+
+    /// Set the number of chars that are printed before the columns, the source
+    /// line is cut off earlier by that much (default: 2)
+    pub fn set_indent(&mut self, indent: usize) {
+        if let Some(ref mut w) = self.width {
+            *w = (*w + DEFAULT_INDENT).saturating_sub(indent);
+        }
+    }
+
+    /// The source line, cut off at the end of the terminal line if its width is known
+    fn line(&self, line: &str) -> String {
+        match self.width {
+            Some(w) => {
+                let used = self.file + 1 + self.lineno + 2 + self.func + 3;
+                truncate_end(line, w.saturating_sub(used).max(MIN_LINE_WIDTH))
+            },
+            None => line.to_string()
+        }
+    }
+
+    pub fn format(&self, meta: &TgMetaNode) -> String {
+        let line = if let Some(ref l) = meta.loc.src_line { l.as_str() } else { "[file not found]" };
+        let line = self.line(line);
+        let file = meta.loc.rel_path().display().to_string();
+        let (file, func) = if self.truncate {
+            (truncate_middle(&file, self.file), truncate_middle(&meta.loc.func, self.func))
+        } else {
+            (file, meta.loc.func.clone())
+        };
+
+        format!("{:>fw$}:{:0lw$}: {:>gw$}:  {}",
+                file,
+                meta.loc.lineno.unwrap_or(0),
+                func,
+                line,
+                fw = self.file,
+                lw = self.lineno,
+                gw = self.func)
+    }
+
+    /// A line of source code around the line of the node, aligned with the
+    /// source lines of the nodes
+    pub fn format_context(&self, lineno: usize, line: &str) -> String {
+        format!("{:>fw$} {:0lw$}- {:>gw$}   {}",
+                "",
+                lineno,
+                "",
+                self.line(line),
+                fw = self.file,
+                lw = self.lineno,
+                gw = self.func)
+    }
+}
+
+/// Pipes the output through $PAGER (default: less) like git does. less quits
+/// right away if the output fits on one screen.
+pub struct Pager {
+    child: Child
+}
+
+impl Pager {
+    /// Returns None if the pager is disabled by PAGER=cat or cannot be started
+    pub fn spawn() -> Option<Pager> {
+        let cmd = env::var("PAGER").unwrap_or_else(|_| "less -FRX".to_string());
+        let mut parts = cmd.split_whitespace();
+        let prog = parts.next()?;
+        if prog == "cat" {
+            return None;
+        }
+
+        Command::new(prog)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()
+            .map(|child| Pager { child })
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.child.stdin.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.child.stdin.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        // close the pipe so that the pager sees the end and wait until the user quits it
+        drop(self.child.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_middle_short() {
+        assert_eq!(truncate_middle("main.c", 6), "main.c");
+        assert_eq!(truncate_middle("main.c", 20), "main.c");
+        assert_eq!(truncate_middle("main.c", 0), "main.c");
+        assert_eq!(truncate_middle("", 3), "");
+    }
+
+    #[test]
+    fn truncate_middle_long() {
+        assert_eq!(truncate_middle("abcdefghij", 9), "abcd…ghij");
+        assert_eq!(truncate_middle("abcdefghij", 5), "ab…ij");
+        assert_eq!(truncate_middle("abcdefghij", 4), "ab…j");
+        assert_eq!(truncate_middle("abcdefghij", 2), "a…");
+        assert_eq!(truncate_middle("abcdefghij", 1), "…");
+    }
+
+    #[test]
+    fn truncate_middle_counts_chars() {
+        assert_eq!(truncate_middle("äöüßäöü", 5), "äö…öü");
+        assert_eq!(truncate_middle("äöü", 3), "äöü");
+        assert_eq!(truncate_middle("src/große/datei.c", 9).chars().count(), 9);
+    }
+
+    #[test]
+    fn truncate_end_long() {
+        assert_eq!(truncate_end("abcdefghij", 10), "abcdefghij");
+        assert_eq!(truncate_end("abcdefghij", 0), "abcdefghij");
+        assert_eq!(truncate_end("abcdefghij", 5), "abcd…");
+        assert_eq!(truncate_end("äöüßäöü", 3), "äö…");
+    }
+
+    #[test]
+    fn source_line_fits_the_width() {
+        let line = "x".repeat(100);
+        let mut cols = Columns { file: 10, lineno: 4, func: 10, truncate: true, width: Some(78) };
+        assert_eq!(format!("  {}", cols.format_context(7, &line)).chars().count(), 80);
+        cols.set_indent(8);
+        assert_eq!(format!("source: {}", cols.format_context(7, &line)).chars().count(), 80);
+        assert_eq!(cols.format_context(7, "x = y;"), format!("{:>10} 0007- {:>10}   x = y;", "", ""));
+    }
+
+    #[test]
+    fn fixed_context_columns() {
+        let cols = Columns::new(&[], None);
+        assert_eq!(cols.format_context(7, "x = y;"),
+                   format!("{:>29} 0007- {:>20}   x = y;", "", ""));
+    }
+}
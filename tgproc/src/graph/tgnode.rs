@@ -4,13 +4,14 @@ use std::cmp::PartialEq;
 use std::cmp::Eq;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::io;
+use std::io::Write;
 use super::regex::Regex;
 use ansi_term::Colour;
 use ansi_term::ANSIString;
 
-use super::meta::TgMetaNode;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum Taint {
     Red,
     Blue,
@@ -30,6 +31,23 @@ impl Taint {
         }
     }
 
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Taint::Red => "red",
+            Taint::Blue => "blue",
+            Taint::Green => "green"
+        }
+    }
+
+    /// Red is stronger than blue and blue stronger than green
+    pub fn strength(&self) -> u8 {
+        match *self {
+            Taint::Red => 2,
+            Taint::Blue => 1,
+            Taint::Green => 0
+        }
+    }
+
     pub fn abbrv(&self) -> &str {
         match *self {
             Taint::Red => "R",
@@ -39,6 +57,74 @@ impl Taint {
     }
 }
 
+/// What a sink does with the tainted value
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub enum SinkKind {
+    Deref,  // a red value is dereferenced
+    Store,  // a red value is used as the address of a store
+    Exit,   // a tainted value is returned by _Exit
+    Cond,   // a red value decides a branch or a ternary
+    Manual  // the sink was set via --mark-sink
+}
+
+impl SinkKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SinkKind::Deref => "deref",
+            SinkKind::Store => "store",
+            SinkKind::Exit => "exit",
+            SinkKind::Cond => "cond",
+            SinkKind::Manual => "manual"
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            SinkKind::Deref => "red value reaches dereference",
+            SinkKind::Store => "red value reaches store address",
+            SinkKind::Exit => "tainted value reaches exit code",
+            SinkKind::Cond => "red value reaches condition",
+            SinkKind::Manual => "tainted value reaches marked sink"
+        }
+    }
+
+    pub fn all() -> Vec<SinkKind> {
+        vec![SinkKind::Deref, SinkKind::Store, SinkKind::Exit, SinkKind::Cond, SinkKind::Manual]
+    }
+
+    pub fn from_name(name: &str) -> Option<SinkKind> {
+        SinkKind::all().into_iter().find(|k| k.name() == name)
+    }
+}
+
+/// Where the memory of a taint source lives
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum SourceClass {
+    Stack,  // an alloca tainted by the ASBDetection pass
+    Heap,   // a pointer returned by the malloc wrapper
+    Global, // in the data sections of the binary
+    Unknown
+}
+
+impl SourceClass {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SourceClass::Stack => "stack",
+            SourceClass::Heap => "heap",
+            SourceClass::Global => "global",
+            SourceClass::Unknown => "unknown"
+        }
+    }
+
+    pub fn all() -> Vec<SourceClass> {
+        vec![SourceClass::Stack, SourceClass::Heap, SourceClass::Global, SourceClass::Unknown]
+    }
+
+    pub fn from_name(name: &str) -> Option<SourceClass> {
+        SourceClass::all().into_iter().find(|c| c.name() == name)
+    }
+}
+
 pub struct TgEdge {
     pub dest : Option<Rc<TgNode>>,
     
@@ -48,7 +134,7 @@ pub struct TgEdge {
 
 impl TgEdge {
     fn new(via: String, dest: Option<Rc<TgNode>>) -> TgEdge {
-        TgEdge { via: via, dest: dest }
+        TgEdge { via, dest }
     }
 }
 
@@ -63,6 +149,8 @@ pub struct TgNode {
     pub idx: usize, // the index of the line in the taintgrind log
     pub preds: Vec<TgEdge>,
     pub sink_reasons: Vec<Rc<TgNode>>,
+    pub sink_kind: Option<SinkKind>,
+    pub source_class: Option<SourceClass>, // only set for sources
     pub taint: Taint
 }
 
@@ -75,9 +163,11 @@ impl TgNode {
                idx: usize,
                graph: &TgNodeMap) -> (Option<String>, Rc<TgNode>) {
         let mut node = TgNode {
-            idx: idx,
+            idx,
             preds: vec![],
             sink_reasons: vec![],
+            sink_kind: None,
+            source_class: None,
             taint: Taint::Green
         };
 
@@ -114,24 +204,25 @@ impl TgNode {
                     }
                     
                     for f in cap.at(3).unwrap().split(", ") {
-                        self.preds.push(TgEdge::new(f.to_string(), graph.get(f).map(|n| n.clone())));
+                        self.preds.push(TgEdge::new(f.to_string(), graph.get(f).cloned()));
                     }
                 } else {
                     // e.g. t78_744 <*- t72_268 (for dereferencing)
                     // or t78_744 <-*- t72_268 (for storing)
                     // we MUST not dereference or store a red value,
                         // however this does not count as taintflow
+                    let kind = if pred.contains(" <-*- ") { SinkKind::Store } else { SinkKind::Deref };
                     for f in cap.at(3).unwrap().split(", ") {
                         if let Some(n) = graph.get(f) {
                             if n.is_red() {
-                                self.sink_reasons.push(n.clone());
+                                self.add_sink_reason(kind, n.clone());
                             }
                         }
                     }
                 }
             } else { // e.g. t54_1741
                 for f in pred.split(", ") {
-                    self.preds.push(TgEdge::new(f.to_string(), graph.get(f).map(|n| n.clone())));
+                    self.preds.push(TgEdge::new(f.to_string(), graph.get(f).cloned()));
                 }
             }
         }
@@ -163,8 +254,8 @@ impl TgNode {
                     cmd.starts_with("Sar")) {
                     self.taint = Taint::Red;
                 } else if cmd.starts_with("Add") {
-                    let mut ngp = self.preds.iter().filter(|&&TgEdge{ ref dest, .. }| {
-                        dest.as_ref().map_or(true, |p| ! p.is_green())
+                    let mut ngp = self.preds.iter().filter(|&TgEdge{ dest, .. }| {
+                        dest.as_ref().is_none_or(|p| ! p.is_green())
                     });
                     
                     if ngp.nth(1).is_some() { // at least two blue predecessors
@@ -174,8 +265,8 @@ impl TgNode {
                     // because we are blue at least one pred is blue
                     // if the other one is blue, too, everything is fine and we are green
                     // if the other one is green nothing is fine and we go red
-                    let mut blue_preds = self.preds.iter().filter(|&&TgEdge{ ref dest, .. }| {
-                        dest.as_ref().map_or(true, |p| p.is_blue())
+                    let mut blue_preds = self.preds.iter().filter(|&TgEdge{ dest, .. }| {
+                        dest.as_ref().is_none_or(|p| p.is_blue())
                     });
 
                     if blue_preds.nth(1).is_some() { // the other one is blue, too
@@ -185,8 +276,8 @@ impl TgNode {
                     }
                 } else {
                     if let Some(cap) = RE_SUB_CMD.captures(cmd) {
-                        let mut ngp = self.preds.iter().filter(|&&TgEdge{ ref dest, .. }| {
-                            dest.as_ref().map_or(true, |p| ! p.is_green())
+                        let mut ngp = self.preds.iter().filter(|&TgEdge{ dest, .. }| {
+                            dest.as_ref().is_none_or(|p| ! p.is_green())
                         });
                         let ngp0 = ngp.next();
 
@@ -195,7 +286,7 @@ impl TgNode {
                         if ngp.next().is_some() {
                             // do not allow (blue - blue)
                             self.taint = Taint::Red;
-                        } else if let Some(&TgEdge { ref via, .. }) = ngp0 {
+                        } else if let Some(TgEdge { via, .. }) = ngp0 {
                             let subtrahend = cap.at(1).unwrap();
                             if (via == subtrahend) {
                                 self.taint = Taint::Red
@@ -219,8 +310,9 @@ impl TgNode {
         if ! self.is_green() {
             if loc_part.contains(" _Exit ") {
                 // we must not allow returning tainted exit values
-                for pred in self.preds.iter().filter_map(|&TgEdge{ref dest, ..}| dest.as_ref()) {
-                    self.sink_reasons.push(pred.clone())
+                let reasons: Vec<Rc<TgNode>> = self.preds.iter().filter_map(|TgEdge{dest, ..}| dest.clone()).collect();
+                for pred in reasons {
+                    self.add_sink_reason(SinkKind::Exit, pred)
                 }
             } else {
                 if let Some(cap) = RE_IF_CMD.captures(cmd_part).or_else(|| RE_TERNARY_CMD.captures(cmd_part)) {
@@ -228,21 +320,37 @@ impl TgNode {
                     
                     // we can safely allow blue taint to reach a condition because
                     // it is either 0 (null) in all variants or a valid pointer (-> true)
-                    for pred in (self.preds.iter()
-                                 .filter(|&&TgEdge{ref via,..}| via == cond)
-                                 .filter_map(|&TgEdge{ref dest,..}| dest.as_ref())
-                                 .filter(|p| p.is_red())) {
-                        self.sink_reasons.push(pred.clone())
+                    let reasons: Vec<Rc<TgNode>> = self.preds.iter()
+                        .filter(|&TgEdge{via,..}| via == cond)
+                        .filter_map(|TgEdge{dest,..}| dest.clone())
+                        .filter(|p| p.is_red())
+                        .collect();
+                    for pred in reasons {
+                        self.add_sink_reason(SinkKind::Cond, pred)
                     }
                 }
             }
         }
     }
 
+    /// Make this node a sink because of pred. If the node is already a sink
+    /// for another reason the first kind of sink is kept.
+    pub fn add_sink_reason(&mut self, kind: SinkKind, pred: Rc<TgNode>) {
+        if self.sink_kind.is_none() {
+            self.sink_kind = Some(kind);
+        }
+        self.sink_reasons.push(pred)
+    }
+
+    pub fn clear_sink_reasons(&mut self) {
+        self.sink_reasons.clear();
+        self.sink_kind = None;
+    }
+
     fn inherit_taint(&mut self) {
         self.taint = if self.is_source() { Taint::Blue } else { Taint::Green };
 
-        for pred in self.preds.iter().filter_map(|&TgEdge{ref dest,..}| dest.as_ref()) {
+        for pred in self.preds.iter().filter_map(|TgEdge{dest,..}| dest.as_ref()) {
             if pred.is_red() {
                 self.taint = Taint::Red;
                 break // once we are red we cannot go back anyway
@@ -275,18 +383,18 @@ impl TgNode {
         self.taint == Taint::Green
     }
 
-    pub fn print(&self, meta: &TgMetaNode, colored: bool) {
+    /// Write the text of the node's line prefixed with its taint
+    pub fn write(&self, out: &mut dyn Write, text: &str, colored: bool) -> io::Result<()> {
         let tnt_str = self.taint.abbrv();
         if colored {
             let clr: Colour = self.taint.color();
-            let meta_str = meta.to_string();
             if self.is_sink() {
-                println!("{} {}", tnt_str, clr.bold().paint(meta_str.as_str()));
+                writeln!(out, "{} {}", tnt_str, clr.bold().paint(text))
             } else {
-                println!("{} {}", tnt_str, clr.paint(meta_str.as_str()));
+                writeln!(out, "{} {}", tnt_str, clr.paint(text))
             }
         } else {
-            println!("{} {}", tnt_str, meta);
+            writeln!(out, "{} {}", tnt_str, text)
         }
     }
 }
@@ -300,7 +408,6 @@ impl PartialEq for TgNode {
 impl Hash for TgNode {
     fn hash<H>(&self, state: &mut H) where H: Hasher {
         state.write_usize(self.idx);
-        state.finish();
     }
 }
 
@@ -1,25 +1,469 @@
 #[macro_use] extern crate lazy_static;
 extern crate ansi_term;
+extern crate libc;
+#[macro_use] extern crate serde_json;
 
 mod cli;
 mod graph;
 
+use std::process;
+use std::fs::File;
+use std::io;
+use std::io::stdout;
+use std::io::BufWriter;
+use std::io::Write;
 use cli::parse_opts;
+use cli::Options;
 use graph::Graph;
 use graph::GraphPrinter;
+use graph::JsonPrinter;
+use graph::SarifPrinter;
+use graph::DotPrinter;
+use graph::HtmlReport;
+use graph::GccPrinter;
+use graph::JunitPrinter;
+use graph::Annotator;
+use graph::Renderer;
+use graph::term;
+use graph::FindingMerger;
+use graph::Baseline;
+use graph::SinkKind;
+use graph::Summary;
+use graph::summary::EXIT_ERROR;
 use graph::meta::TgMetaDb;
 use graph::meta::SimpleMetaDB;
+use graph::meta;
+
+/// The formats that can be given with -o
+const OUTPUT_FORMATS: [&str; 8] = ["text", "json", "jsonl", "sarif", "dot", "gcc", "junit", "html"];
 
 fn main() {
     let cli_opts = parse_opts();
 
+    let fail_on = match parse_sink_kinds(&cli_opts.fail_on) {
+        Ok(kinds) => kinds,
+        Err(x) => {
+            eprintln!("{}", x);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    if !["text", "json", "jsonl", "sarif", "dot", "gcc", "junit"].contains(&cli_opts.format.as_str()) {
+        eprintln!("Unknown output format: {}", cli_opts.format);
+        process::exit(EXIT_ERROR);
+    }
+
+    if cli_opts.command == "report" && cli_opts.html_dir.is_empty() && cli_opts.outputs.is_empty() {
+        eprintln!("report: --html <dir> is missing");
+        process::exit(EXIT_ERROR);
+    }
+
+    let outputs = match parse_outputs(&cli_opts) {
+        Ok(outputs) => outputs,
+        Err(x) => {
+            eprintln!("{}", x);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    match parse_path_maps(&cli_opts.path_maps) {
+        Ok(path_maps) => meta::sources::configure(&cli_opts.source_roots, path_maps),
+        Err(x) => {
+            eprintln!("{}", x);
+            process::exit(EXIT_ERROR);
+        }
+    }
+
+    if !cli_opts.outputs.is_empty() && (cli_opts.logfiles.len() > 1 || cli_opts.compare_opt) {
+        eprintln!("-o can only be used with a single log");
+        process::exit(EXIT_ERROR);
+    }
+
+    if let Some(option) = unsupported_with_merge(&cli_opts) {
+        eprintln!("{} cannot be used with several logs or --compare-opt", option);
+        process::exit(EXIT_ERROR);
+    }
+
+    let summary = if cli_opts.format == "junit" && cli_opts.logfiles.len() > 1 {
+        junit_logs(cli_opts.clone())
+    } else if cli_opts.logfiles.len() > 1 || cli_opts.compare_opt {
+        merge_logs(cli_opts.clone())
+    } else {
+        process_log(cli_opts.clone(), &outputs)
+    };
+
+    if cli_opts.verbosity > 0 {
+        summary.print();
+    }
+
+    process::exit(summary.exit_code(&fail_on));
+}
+
+/// The option that the merged report of several logs does not support, if any.
+/// That report only exists as text; JUnit has a testsuite per log and honours
+/// the baseline, but cannot compare optimisation levels. The HTML report of
+/// the report command and the annotated source file of the annotate command
+/// are rendered from a single log.
+fn unsupported_with_merge(cli_opts: &Options) -> Option<String> {
+    let junit = cli_opts.format == "junit" && !cli_opts.compare_opt;
+
+    if cli_opts.logfiles.len() < 2 && !cli_opts.compare_opt {
+        None
+    } else if cli_opts.command == "report" || cli_opts.command == "annotate" {
+        Some(cli_opts.command.clone())
+    } else if cli_opts.format != "text" && !junit {
+        Some(format!("--format={}", cli_opts.format))
+    } else if !cli_opts.write_baseline.is_empty() {
+        Some("--write-baseline".to_string())
+    } else if !cli_opts.baseline.is_empty() && !junit {
+        Some("--baseline".to_string())
+    } else {
+        None
+    }
+}
+
+/// Split the -o arguments into format and path. Without -o there is one output
+/// on stdout in the --format, the HTML report of the report command or the
+/// annotated source file of the annotate command.
+fn parse_outputs(cli_opts: &Options) -> Result<Vec<(String, String)>, String> {
+    if cli_opts.command == "annotate" {
+        if !cli_opts.outputs.is_empty() {
+            return Err("-o cannot be used with annotate".to_string());
+        }
+        return Ok(vec![("annotate".to_string(), "-".to_string())]);
+    }
+
+    if cli_opts.outputs.is_empty() {
+        if cli_opts.command == "report" {
+            return Ok(vec![("html".to_string(), cli_opts.html_dir.clone())]);
+        }
+        return Ok(vec![(cli_opts.format.clone(), "-".to_string())]);
+    }
+
+    cli_opts.outputs.iter().map(|output| {
+        let (format, path) = match output.find(':') {
+            Some(pos) => (&output[..pos], &output[pos+1..]),
+            None => (output.as_str(), "-")
+        };
+
+        if !OUTPUT_FORMATS.contains(&format) {
+            Err(format!("Unknown output format: {}", format))
+        } else if path.is_empty() || (format == "html" && path == "-") {
+            Err(format!("Missing path in output: {}", output))
+        } else {
+            Ok((format.to_string(), path.to_string()))
+        }
+    }).collect()
+}
+
+/// Render one output of the graph; the path "-" is stdout
+fn render_output(graph: &Graph,
+                 meta_db: &mut SimpleMetaDB,
+                 baseline: Option<&Baseline>,
+                 format: &str,
+                 path: &str) -> io::Result<Summary> {
+    let to_stdout = path == "-" && format != "html";
+    // the width has to be measured before the pager takes over stdout
+    let width = if to_stdout { term::width() } else { None };
+
+    let mut out: Box<dyn Write> = if to_stdout && format == "text" && graph.options.pager && term::stdout_is_tty() {
+        match term::Pager::spawn() {
+            Some(pager) => Box::new(pager),
+            None => Box::new(stdout())
+        }
+    } else if path == "-" || format == "html" {
+        Box::new(stdout())
+    } else {
+        Box::new(BufWriter::new(File::create(path)?))
+    };
+
+    let mut renderer: Box<dyn Renderer> = match format {
+        "text" => {
+            let mut printer = GraphPrinter::<SimpleMetaDB>::new(graph, meta_db);
+            // no escape sequences in files
+            printer.set_color(graph.options.color && to_stdout);
+            printer.set_width(width);
+            Box::new(printer)
+        },
+        "gcc" => Box::new(GccPrinter::new(graph, meta_db)),
+        "dot" => Box::new(DotPrinter::new(graph, meta_db)),
+        "sarif" => Box::new(SarifPrinter::new(graph, meta_db)),
+        "html" => Box::new(HtmlReport::new(graph, meta_db, path)),
+        "junit" => Box::new(JunitPrinter::new(graph, meta_db)),
+        "annotate" => Box::new(Annotator::new(graph, &graph.options.annotate_file)),
+        _ => Box::new(JsonPrinter::new(graph, meta_db, format == "jsonl"))
+    };
+
+    if let Some(b) = baseline {
+        renderer.findings().set_baseline(b.clone());
+    }
+
+    match renderer.render(&mut *out).and_then(|_| out.flush()) {
+        // the pager was quit or the output was piped to e.g. head
+        Err(ref x) if to_stdout && x.kind() == io::ErrorKind::BrokenPipe => (),
+        Err(x) => return Err(x),
+        Ok(()) => ()
+    }
+    Ok(renderer.summary().clone())
+}
+
+/// Parse a comma separated list of sink kinds
+fn parse_sink_kinds(kinds: &str) -> Result<Vec<SinkKind>, String> {
+    match kinds {
+        "all" => Ok(SinkKind::all()),
+        "none" | "" => Ok(vec![]),
+        _ => kinds.split(',')
+            .map(|k| SinkKind::from_name(k.trim()).ok_or(format!("Unknown sink kind: {}", k)))
+            .collect()
+    }
+}
+
+/// Split the --path-map arguments into old and new prefix
+fn parse_path_maps(path_maps: &[String]) -> Result<Vec<(String, String)>, String> {
+    path_maps.iter().map(|map| match map.find('=') {
+        Some(pos) if pos > 0 => Ok((map[..pos].to_string(), map[pos+1..].to_string())),
+        _ => Err(format!("Invalid path map (expected old=new): {}", map))
+    }).collect()
+}
+
+fn merge_logs(cli_opts: Options) -> Summary {
+    // sources are not comparable between different builds so we only match sinks there
+    let mut merger = FindingMerger::new(! cli_opts.compare_opt, cli_opts.color);
+    let mut errors = 0;
+
+    for logfile in cli_opts.logfiles.iter() {
+        let mut run_opts = cli_opts.clone();
+        run_opts.logfile = logfile.clone();
+
+        // each run gets its own meta db so that we only keep one log in memory
+        let mut meta_db = SimpleMetaDB::new();
+        let name = if cli_opts.compare_opt {
+            FindingMerger::opt_level_of(logfile)
+        } else {
+            logfile.clone()
+        };
+
+        match Graph::new(run_opts, Some(&mut meta_db)) {
+            Ok(graph) => merger.add_run(&name, &graph, &mut meta_db),
+            Err(x) => {
+                eprintln!("{}: {}", logfile, x);
+                errors += 1;
+            }
+        }
+    }
+
+    if !cli_opts.mark_taint {
+        // else the taint has already been printed while parsing
+        merger.set_matrix(cli_opts.compare_opt);
+
+        let out = stdout();
+        let mut out = out.lock();
+        match merger.render(&mut out).and_then(|_| out.flush()) {
+            // the output was piped to e.g. head
+            Err(ref x) if x.kind() == io::ErrorKind::BrokenPipe => (),
+            Err(x) => {
+                eprintln!("{}", x);
+                errors += 1;
+            },
+            Ok(()) => ()
+        }
+    }
+
+    let mut summary = merger.summary().clone();
+    summary.errors += errors;
+    summary
+}
+
+/// Write one JUnit testsuite per log
+fn junit_logs(cli_opts: Options) -> Summary {
+    let mut summary = Summary::new();
+
+    let baseline = if cli_opts.baseline.is_empty() {
+        None
+    } else {
+        match Baseline::load(&cli_opts.baseline) {
+            Ok(b) => Some(b),
+            Err(x) => {
+                eprintln!("{}: {}", cli_opts.baseline, x);
+                summary.errors += 1;
+                return summary;
+            }
+        }
+    };
+
+    let out = stdout();
+    let mut out = out.lock();
+    let written = JunitPrinter::<SimpleMetaDB>::write_header(&mut out).and_then(|_| {
+        for logfile in cli_opts.logfiles.iter() {
+            let mut run_opts = cli_opts.clone();
+            run_opts.logfile = logfile.clone();
+
+            let mut meta_db = SimpleMetaDB::new();
+            match Graph::new(run_opts, Some(&mut meta_db)) {
+                Ok(graph) => {
+                    let mut printer = JunitPrinter::new(&graph, &mut meta_db);
+                    if let Some(ref b) = baseline {
+                        printer.findings().set_baseline(b.clone());
+                    }
+                    printer.write_suite(&mut out)?;
+                    summary.add(printer.summary());
+                },
+                Err(x) => {
+                    eprintln!("{}: {}", logfile, x);
+                    summary.errors += 1;
+                    JunitPrinter::<SimpleMetaDB>::write_error_suite(&mut out, logfile, &x.to_string())?;
+                }
+            }
+        }
+        JunitPrinter::<SimpleMetaDB>::write_footer(&mut out)
+    });
+
+    if let Err(x) = written {
+        eprintln!("{}", x);
+        summary.errors += 1;
+    }
+    summary
+}
+
+fn process_log(cli_opts: Options, outputs: &[(String, String)]) -> Summary {
     let mut meta_db = SimpleMetaDB::new();
+    let mut summary = Summary::new();
+    let logfile = cli_opts.logfile.clone();
+
     match Graph::new(cli_opts, Some(&mut meta_db)) {
         Ok(graph) => {
-            if ! graph.options.mark_taint {
-                GraphPrinter::<SimpleMetaDB>::new(&graph, &mut meta_db).print_traces();
+            if ! graph.options.write_baseline.is_empty() {
+                let mut printer = GraphPrinter::<SimpleMetaDB>::new(&graph, &mut meta_db);
+                if let Err(x) = printer.write_baseline(&graph.options.write_baseline) {
+                    eprintln!("{}: {}", graph.options.write_baseline, x);
+                    summary.errors += 1;
+                    return summary;
+                }
+            }
+
+            let mut baseline = None;
+            if ! graph.options.baseline.is_empty() {
+                match Baseline::load(&graph.options.baseline) {
+                    Ok(b) => baseline = Some(b),
+                    Err(x) => {
+                        eprintln!("{}: {}", graph.options.baseline, x);
+                        summary.errors += 1;
+                        return summary;
+                    }
+                }
+            }
+
+            if graph.options.mark_taint {
+                summary.add_graph(&graph);
+            } else {
+                // all outputs report the same findings, so the summary of the first one is used
+                let mut rendered = None;
+                let mut errors = 0;
+
+                for (format, path) in outputs.iter() {
+                    match render_output(&graph, &mut meta_db, baseline.as_ref(), format, path) {
+                        Ok(s) => if rendered.is_none() { rendered = Some(s) },
+                        Err(x) => {
+                            if path == "-" {
+                                eprintln!("{}", x);
+                            } else {
+                                eprintln!("{}: {}", path, x);
+                            }
+                            errors += 1;
+                        }
+                    }
+                }
+
+                summary = rendered.unwrap_or_else(|| {
+                    let mut s = Summary::new();
+                    s.add_graph(&graph);
+                    s
+                });
+                summary.errors += errors;
             }
         },
-        Err(x) => println!("{}", x)
+        Err(x) => {
+            eprintln!("{}: {}", logfile, x);
+            summary.errors += 1;
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(command: &str, outputs: &[&str]) -> Options {
+        let mut cli_opts = Options::new();
+        cli_opts.command = command.to_string();
+        cli_opts.html_dir = "report".to_string();
+        cli_opts.outputs = outputs.iter().map(|o| o.to_string()).collect();
+        cli_opts
+    }
+
+    fn outputs(list: &[(&str, &str)]) -> Result<Vec<(String, String)>, String> {
+        Ok(list.iter().map(|&(f, p)| (f.to_string(), p.to_string())).collect())
+    }
+
+    #[test]
+    fn parse_outputs_default() {
+        assert_eq!(parse_outputs(&opts("", &[])), outputs(&[("text", "-")]));
+
+        let mut cli_opts = opts("", &[]);
+        cli_opts.format = "sarif".to_string();
+        assert_eq!(parse_outputs(&cli_opts), outputs(&[("sarif", "-")]));
+
+        assert_eq!(parse_outputs(&opts("report", &[])), outputs(&[("html", "report")]));
+        assert_eq!(parse_outputs(&opts("annotate", &[])), outputs(&[("annotate", "-")]));
+    }
+
+    #[test]
+    fn parse_outputs_list() {
+        assert_eq!(parse_outputs(&opts("", &["text", "sarif:out.sarif", "junit:x.xml", "html:r"])),
+                   outputs(&[("text", "-"), ("sarif", "out.sarif"), ("junit", "x.xml"), ("html", "r")]));
+        assert_eq!(parse_outputs(&opts("", &["json:-", "dot:c:/tmp/a.dot"])),
+                   outputs(&[("json", "-"), ("dot", "c:/tmp/a.dot")]));
+    }
+
+    #[test]
+    fn parse_outputs_errors() {
+        assert_eq!(parse_outputs(&opts("", &["xml:a.xml"])), Err("Unknown output format: xml".to_string()));
+        assert_eq!(parse_outputs(&opts("", &["json:"])), Err("Missing path in output: json:".to_string()));
+        assert_eq!(parse_outputs(&opts("", &["html"])), Err("Missing path in output: html".to_string()));
+        assert_eq!(parse_outputs(&opts("", &["html:-"])), Err("Missing path in output: html:-".to_string()));
+        assert_eq!(parse_outputs(&opts("annotate", &["text"])),
+                   Err("-o cannot be used with annotate".to_string()));
+    }
+
+    #[test]
+    fn merge_options() {
+        let mut cli_opts = opts("", &[]);
+        cli_opts.format = "json".to_string();
+        cli_opts.baseline = "b.txt".to_string();
+        assert_eq!(unsupported_with_merge(&cli_opts), None);
+
+        cli_opts.logfiles = vec!["a.log".to_string(), "b.log".to_string()];
+        assert_eq!(unsupported_with_merge(&cli_opts), Some("--format=json".to_string()));
+
+        cli_opts.format = "junit".to_string();
+        assert_eq!(unsupported_with_merge(&cli_opts), None);
+        cli_opts.compare_opt = true;
+        assert_eq!(unsupported_with_merge(&cli_opts), Some("--format=junit".to_string()));
+
+        cli_opts.format = "text".to_string();
+        assert_eq!(unsupported_with_merge(&cli_opts), Some("--baseline".to_string()));
+        cli_opts.write_baseline = "b.txt".to_string();
+        assert_eq!(unsupported_with_merge(&cli_opts), Some("--write-baseline".to_string()));
+
+        let mut cli_opts = opts("report", &[]);
+        assert_eq!(unsupported_with_merge(&cli_opts), None);
+        cli_opts.logfiles = vec!["a.log".to_string(), "b.log".to_string()];
+        assert_eq!(unsupported_with_merge(&cli_opts), Some("report".to_string()));
+
+        let mut cli_opts = opts("annotate", &[]);
+        cli_opts.logfiles = vec!["a.log".to_string(), "b.log".to_string()];
+        assert_eq!(unsupported_with_merge(&cli_opts), Some("annotate".to_string()));
     }
 }